@@ -353,6 +353,9 @@ mod tests {
 
 		let withdraw_amount = 100;
 
+		// HINT: xcm_simulator_pba::query registers the query on ParaA and builds the
+		// `ReportHolding` for you, and `query::take_response` gives you the response afterwards
+
 		/* Fill in here */
 	}
 
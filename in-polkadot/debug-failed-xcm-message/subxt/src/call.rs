@@ -0,0 +1,44 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Decodes the calls carried by `Transact` instructions against the metadata of the chain they
+//! are executed on.
+
+use std::{error::Error, fmt, path::Path};
+
+use serde::Serialize;
+
+use crate::dynamic::Metadata;
+
+/// A call decoded into its pallet, name and arguments.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DecodedCall {
+	pub pallet: String,
+	pub call: String,
+	pub args: serde_json::Value,
+}
+
+impl fmt::Display for DecodedCall {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}.{} {}", self.pallet, self.call, self.args)
+	}
+}
+
+/// Decodes calls using the metadata of a chain.
+pub struct CallDecoder {
+	metadata: Metadata,
+}
+
+impl CallDecoder {
+	/// Reads SCALE encoded metadata, as returned by `state_getMetadata`, from `path`.
+	pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+		Ok(Self { metadata: Metadata::from_file(path)? })
+	}
+
+	/// Decodes `call`, encoded as the pallet index followed by the pallet's call enum.
+	pub fn decode(&self, call: &[u8]) -> Result<DecodedCall, Box<dyn Error>> {
+		let (pallet, variant) = self.metadata.decode_call(call)?;
+		Ok(DecodedCall { pallet, call: variant.name, args: serde_json::to_value(variant.values)? })
+	}
+}
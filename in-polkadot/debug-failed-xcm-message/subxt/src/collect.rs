@@ -0,0 +1,255 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Collects the XCM messages carried by a relay chain block.
+
+use std::error::Error;
+
+use codec::Decode;
+use serde::Serialize;
+
+use crate::polkadot::runtime_types::{
+	polkadot_parachain::primitives::Id as ParaId, polkadot_runtime::RuntimeCall,
+	polkadot_runtime_parachains::paras_inherent::pallet::Call as ParasInherentCall,
+};
+
+/// The transport an XCM message was sent over.
+#[derive(clap::ArgEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+	/// Messages sent by a para to the relay.
+	#[default]
+	Ump,
+	/// Messages queued by the relay for a para.
+	Dmp,
+	/// Messages sent by a para to another para, through the relay.
+	Hrmp,
+}
+
+/// The format byte prefixing the data of an HRMP message made of concatenated `VersionedXcm`s.
+const CONCATENATED_VERSIONED_XCM: u8 = 0;
+
+/// An XCM message found in a block, still encoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawMessage {
+	pub channel: Channel,
+	/// The para sending the message, `None` for downward messages sent by the relay.
+	pub sender: Option<u32>,
+	/// The para receiving the message, `None` for upward messages received by the relay.
+	pub recipient: Option<u32>,
+	/// The position of the message among the ones sent over the same channel.
+	pub index: u32,
+	/// The encoded `VersionedXcm`.
+	pub data: Vec<u8>,
+}
+
+impl RawMessage {
+	/// The para the message is inspected for: its sender, or its recipient for downward messages.
+	pub fn para_id(&self) -> Option<u32> {
+		self.sender.or(self.recipient)
+	}
+}
+
+/// Which messages to keep.
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+	/// The paras the messages may be sent by, any para if empty.
+	pub senders: Vec<u32>,
+	pub recipient: Option<u32>,
+}
+
+impl Filter {
+	fn matches(&self, sender: Option<u32>, recipient: Option<u32>) -> bool {
+		(self.senders.is_empty() || sender.is_some_and(|id| self.senders.contains(&id))) &&
+			self.recipient.is_none_or(|id| recipient == Some(id))
+	}
+}
+
+/// The messages a backed candidate commits to.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Candidate {
+	pub para_id: u32,
+	pub upward_messages: Vec<Vec<u8>>,
+	/// The recipient and data of each horizontal message.
+	pub horizontal_messages: Vec<(u32, Vec<u8>)>,
+}
+
+/// Reads the candidates backed by `extrinsic`, none unless it is the parachains inherent.
+pub fn candidates(extrinsic: &[u8]) -> Vec<Candidate> {
+	// The first byte is the extrinsic version. Only a malformed dump holds an empty extrinsic.
+	let Some((_version, mut sliced)) = extrinsic.split_first() else {
+		eprintln!("skipping empty extrinsic");
+		return Vec::new()
+	};
+
+	let decoded: Result<RuntimeCall, codec::Error> = Decode::decode(&mut sliced);
+	let data = match decoded {
+		Ok(RuntimeCall::ParaInherent(ParasInherentCall::enter { data })) => data,
+		_ => return Vec::new(),
+	};
+
+	data.backed_candidates
+		.into_iter()
+		.map(|candidate| {
+			let ParaId(para_id) = candidate.candidate.descriptor.para_id;
+			let commitments = candidate.candidate.commitments;
+			Candidate {
+				para_id,
+				upward_messages: commitments.upward_messages,
+				horizontal_messages: commitments
+					.horizontal_messages
+					.into_iter()
+					.map(|message| (message.recipient.0, message.data))
+					.collect(),
+			}
+		})
+		.collect()
+}
+
+/// Collects the upward or horizontal messages of the candidates backed in `extrinsics`.
+pub fn from_extrinsics(
+	extrinsics: &[Vec<u8>],
+	channel: Channel,
+	filter: &Filter,
+) -> Vec<RawMessage> {
+	let candidates = extrinsics.iter().flat_map(|extrinsic| candidates(extrinsic));
+	from_candidates(candidates, channel, filter, |data| {
+		Ok(xcm_decoder::split_concatenated::<crate::VersionedXcm>(data)?)
+	})
+}
+
+/// Collects the upward or horizontal messages of `candidates`, splitting the concatenated
+/// `VersionedXcm`s of horizontal messages with `split`.
+pub fn from_candidates(
+	candidates: impl IntoIterator<Item = Candidate>,
+	channel: Channel,
+	filter: &Filter,
+	split: impl Fn(&[u8]) -> Result<Vec<Vec<u8>>, Box<dyn Error>>,
+) -> Vec<RawMessage> {
+	let mut messages = Vec::new();
+
+	for candidate in candidates {
+		let sender = candidate.para_id;
+
+		match channel {
+			Channel::Ump if filter.matches(Some(sender), None) =>
+				messages.extend(candidate.upward_messages.into_iter().enumerate().map(
+					|(index, data)| RawMessage {
+						channel,
+						sender: Some(sender),
+						recipient: None,
+						index: index as u32,
+						data,
+					},
+				)),
+			Channel::Hrmp => {
+				let mut index = 0;
+				for (recipient, data) in candidate.horizontal_messages {
+					if !filter.matches(Some(sender), Some(recipient)) {
+						continue
+					}
+					for data in split_hrmp_data(&data, &split) {
+						messages.push(RawMessage {
+							channel,
+							sender: Some(sender),
+							recipient: Some(recipient),
+							index,
+							data,
+						});
+						index += 1;
+					}
+				}
+			},
+			_ => {},
+		}
+	}
+
+	messages
+}
+
+/// Wraps the downward messages queued for `para_id`.
+pub fn from_downward_queue(para_id: u32, queue: Vec<(u32, Vec<u8>)>) -> Vec<RawMessage> {
+	queue
+		.into_iter()
+		.enumerate()
+		.map(|(index, (_sent_at, data))| RawMessage {
+			channel: Channel::Dmp,
+			sender: None,
+			recipient: Some(para_id),
+			index: index as u32,
+			data,
+		})
+		.collect()
+}
+
+/// Splits the data of an HRMP message into the encoded `VersionedXcm`s it concatenates.
+///
+/// Blobs and signals are not XCM messages and are skipped.
+fn split_hrmp_data(
+	data: &[u8],
+	split: impl Fn(&[u8]) -> Result<Vec<Vec<u8>>, Box<dyn Error>>,
+) -> Vec<Vec<u8>> {
+	let remaining = match data.split_first() {
+		Some((&CONCATENATED_VERSIONED_XCM, remaining)) => remaining,
+		Some((format, _)) => {
+			eprintln!("skipping HRMP message with unsupported format {}", format);
+			return Vec::new()
+		},
+		None => return Vec::new(),
+	};
+
+	split(remaining).unwrap_or_else(|error| {
+		eprintln!("unable to split HRMP message: {}", error);
+		Vec::new()
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::polkadot::runtime_types::xcm::{v2, VersionedXcm};
+	use codec::Encode;
+
+	#[test]
+	fn splits_concatenated_hrmp_messages() {
+		let first = VersionedXcm::V2(v2::Xcm(vec![v2::Instruction::ClearOrigin])).encode();
+		let second = VersionedXcm::V2(v2::Xcm(vec![v2::Instruction::RefundSurplus])).encode();
+		let data = [&[CONCATENATED_VERSIONED_XCM][..], &first, &second].concat();
+
+		let split = |data: &[u8]| -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+			Ok(xcm_decoder::split_concatenated::<VersionedXcm>(data)?)
+		};
+		assert_eq!(split_hrmp_data(&data, split), vec![first, second]);
+	}
+
+	#[test]
+	fn skips_hrmp_blobs() {
+		let split =
+			|_: &[u8]| -> Result<Vec<Vec<u8>>, Box<dyn Error>> { panic!("blobs are not split") };
+		assert!(split_hrmp_data(&[1, 2, 3], split).is_empty());
+		assert!(split_hrmp_data(&[], split).is_empty());
+	}
+
+	#[test]
+	fn skips_empty_extrinsics() {
+		assert_eq!(candidates(&[]), Vec::new());
+	}
+
+	#[test]
+	fn filters_by_sender_and_recipient() {
+		let filter = Filter { senders: vec![1000], recipient: None };
+		assert!(filter.matches(Some(1000), Some(2000)));
+		assert!(!filter.matches(Some(2000), Some(1000)));
+
+		let filter = Filter { senders: vec![1000], recipient: Some(2000) };
+		assert!(filter.matches(Some(1000), Some(2000)));
+		assert!(!filter.matches(Some(1000), Some(2004)));
+		assert!(Filter::default().matches(None, Some(2004)));
+
+		let filter = Filter { senders: vec![1000, 2000], recipient: None };
+		assert!(filter.matches(Some(1000), None));
+		assert!(filter.matches(Some(2000), None));
+		assert!(!filter.matches(Some(2004), None));
+	}
+}
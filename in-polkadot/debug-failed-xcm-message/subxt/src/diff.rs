@@ -0,0 +1,223 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Compares a sent XCM message against the expected one, or against a JSON template.
+//!
+//! Messages are compared in their JSON form (see [`crate::dynamic::to_json`]), which a template
+//! starts from: any value of the template may be replaced by [`WILDCARD`] to accept anything.
+//! Lists, e.g. of instructions, are aligned before being compared, so an inserted or missing
+//! instruction is reported on its own.
+
+use std::fmt;
+
+use serde_json::Value as Json;
+
+/// The template value matching any value.
+pub const WILDCARD: &str = "*";
+
+/// A value that differs between the sent and the expected message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Difference {
+	/// Where the value is, e.g. `V3[1].WithdrawAsset[0].fun.Fungible`.
+	pub path: String,
+	/// The sent value, `None` if it is missing.
+	pub sent: Option<Json>,
+	/// The expected value, `None` if it was not expected.
+	pub expected: Option<Json>,
+}
+
+impl fmt::Display for Difference {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let show = |value: &Option<Json>| match value {
+			Some(value) => value.to_string(),
+			None => "nothing".into(),
+		};
+		write!(f, "{}: sent {}, expected {}", self.path, show(&self.sent), show(&self.expected))
+	}
+}
+
+/// Lists the differences between `sent` and `expected`.
+pub fn diff(sent: &Json, expected: &Json) -> Vec<Difference> {
+	let mut differences = Vec::new();
+	compare(String::new(), Some(sent), Some(expected), &mut differences);
+	differences
+}
+
+fn compare(
+	path: String,
+	sent: Option<&Json>,
+	expected: Option<&Json>,
+	differences: &mut Vec<Difference>,
+) {
+	let differ = |differences: &mut Vec<Difference>| {
+		differences.push(Difference {
+			path: path.clone(),
+			sent: sent.cloned(),
+			expected: expected.cloned(),
+		})
+	};
+
+	match (sent, expected) {
+		(_, Some(Json::String(wildcard))) if wildcard == WILDCARD => {},
+		// Different variants, e.g. instructions, differ as a whole.
+		(Some(Json::Object(sent)), Some(Json::Object(expected)))
+			if sent.len() == 1 && expected.len() == 1 && sent.keys().ne(expected.keys()) =>
+			differ(differences),
+		(Some(Json::Object(sent)), Some(Json::Object(expected))) => {
+			for key in expected.keys().chain(sent.keys().filter(|key| !expected.contains_key(*key)))
+			{
+				compare(join(&path, key), sent.get(key), expected.get(key), differences);
+			}
+		},
+		(Some(Json::Array(sent)), Some(Json::Array(expected))) =>
+			for (sent_index, expected_index) in align(sent, expected) {
+				let index = sent_index.or(expected_index).expect("aligned elements are on a side");
+				compare(
+					format!("{}[{}]", path, index),
+					sent_index.map(|index| &sent[index]),
+					expected_index.map(|index| &expected[index]),
+					differences,
+				);
+			},
+		(sent, expected) if sent == expected => {},
+		_ => differ(differences),
+	}
+}
+
+/// Pairs the indices of `sent` and `expected`, keeping their longest common subsequence of matching
+/// elements aligned. The elements left between two matches are paired in order, and the ones a side
+/// has more of are paired with `None`.
+fn align(sent: &[Json], expected: &[Json]) -> Vec<(Option<usize>, Option<usize>)> {
+	let matches: Vec<Vec<bool>> = sent
+		.iter()
+		.map(|sent| expected.iter().map(|expected| diff(sent, expected).is_empty()).collect())
+		.collect();
+	// `common[i][j]` is the length of the longest common subsequence of `sent[i..]` and
+	// `expected[j..]`.
+	let mut common = vec![vec![0; expected.len() + 1]; sent.len() + 1];
+	for i in (0..sent.len()).rev() {
+		for j in (0..expected.len()).rev() {
+			common[i][j] = match matches[i][j] {
+				true => common[i + 1][j + 1] + 1,
+				false => common[i + 1][j].max(common[i][j + 1]),
+			};
+		}
+	}
+
+	let mut aligned = Vec::new();
+	let (mut unmatched_sent, mut unmatched_expected) = (Vec::new(), Vec::new());
+	let (mut i, mut j) = (0, 0);
+	while i < sent.len() || j < expected.len() {
+		if i < sent.len() && j < expected.len() && matches[i][j] {
+			pair(&mut aligned, &mut unmatched_sent, &mut unmatched_expected);
+			aligned.push((Some(i), Some(j)));
+			i += 1;
+			j += 1;
+		} else if j == expected.len() || (i < sent.len() && common[i + 1][j] >= common[i][j + 1]) {
+			unmatched_sent.push(i);
+			i += 1;
+		} else {
+			unmatched_expected.push(j);
+			j += 1;
+		}
+	}
+	pair(&mut aligned, &mut unmatched_sent, &mut unmatched_expected);
+	aligned
+}
+
+/// Moves the unmatched indices to `aligned`, pairing them in order.
+fn pair(
+	aligned: &mut Vec<(Option<usize>, Option<usize>)>,
+	sent: &mut Vec<usize>,
+	expected: &mut Vec<usize>,
+) {
+	for index in 0..sent.len().max(expected.len()) {
+		aligned.push((sent.get(index).copied(), expected.get(index).copied()));
+	}
+	sent.clear();
+	expected.clear();
+}
+
+fn join(path: &str, key: &str) -> String {
+	match path {
+		"" => key.into(),
+		path => format!("{}.{}", path, key),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	fn withdraw(amount: u64, parents: u8) -> Json {
+		json!({ "WithdrawAsset": [{
+			"id": { "Concrete": { "parents": parents, "interior": "Here" } },
+			"fun": { "Fungible": amount },
+		}] })
+	}
+
+	#[test]
+	fn finds_amount_and_location_differences() {
+		let sent = json!({ "V3": [withdraw(100, 1), "ClearOrigin"] });
+		let expected = json!({ "V3": [withdraw(200, 0), "ClearOrigin"] });
+
+		assert_eq!(
+			diff(&sent, &expected)
+				.into_iter()
+				.map(|difference| difference.to_string())
+				.collect::<Vec<_>>(),
+			vec![
+				"V3[0].WithdrawAsset[0].fun.Fungible: sent 100, expected 200",
+				"V3[0].WithdrawAsset[0].id.Concrete.parents: sent 1, expected 0",
+			]
+		);
+	}
+
+	#[test]
+	fn reports_different_and_missing_instructions() {
+		let sent = json!({ "V3": ["ClearOrigin", "ClearTopic"] });
+		let expected = json!({ "V3": [withdraw(100, 1), "ClearOrigin", "ClearError"] });
+
+		assert_eq!(
+			diff(&sent, &expected),
+			vec![
+				Difference { path: "V3[0]".into(), sent: None, expected: Some(withdraw(100, 1)) },
+				Difference {
+					path: "V3[1]".into(),
+					sent: Some(json!("ClearTopic")),
+					expected: Some(json!("ClearError")),
+				},
+			]
+		);
+	}
+
+	#[test]
+	fn reports_inserted_instruction_alone() {
+		let deposit = |beneficiary| json!({ "DepositAsset": { "beneficiary": beneficiary } });
+		let sent =
+			json!({ "V3": [withdraw(100, 1), "ClearOrigin", deposit(json!(1)), "ClearTopic"] });
+		let expected = json!({ "V3": [withdraw(100, 1), deposit(json!("*")), "ClearTopic"] });
+
+		assert_eq!(
+			diff(&sent, &expected),
+			vec![Difference {
+				path: "V3[1]".into(),
+				sent: Some(json!("ClearOrigin")),
+				expected: None,
+			}]
+		);
+	}
+
+	#[test]
+	fn wildcards_match_anything() {
+		let sent = json!({ "V3": [withdraw(100, 1), "ClearOrigin"] });
+		let expected = json!({ "V3": [
+			{ "WithdrawAsset": [{ "id": "*", "fun": { "Fungible": "*" } }] },
+			"*",
+		] });
+
+		assert!(diff(&sent, &expected).is_empty());
+	}
+}
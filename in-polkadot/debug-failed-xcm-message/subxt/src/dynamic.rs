@@ -0,0 +1,481 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Decoding driven by a metadata file chosen at runtime rather than the bundled Polkadot metadata,
+//! so blocks of any relay (Kusama, Rococo, Westend...) can be inspected.
+//!
+//! Values are decoded into [`scale_value::Value`]s and read by field name, which works as long as
+//! the relay keeps the names Polkadot uses.
+
+use std::{error::Error, fmt::Write, fs, path::Path};
+
+use codec::Decode;
+use serde_json::Value as Json;
+use subxt::ext::{
+	frame_metadata::{RuntimeMetadata, RuntimeMetadataPrefixed, RuntimeMetadataV14},
+	scale_value::{
+		self,
+		scale::{decode_as_type, TypeId},
+		Composite, Primitive, ValueDef, Variant,
+	},
+};
+
+use crate::{
+	collect::Candidate,
+	message::{Instruction, Message},
+};
+
+/// A value decoded against the metadata.
+pub type Value = scale_value::Value<TypeId>;
+
+/// The bit set in the version byte of signed extrinsics.
+const SIGNED_EXTRINSIC: u8 = 0b1000_0000;
+
+/// The metadata of a chain, to decode its blocks and messages.
+pub struct Metadata {
+	metadata: RuntimeMetadataV14,
+	/// The type of `VersionedXcm`, if the chain has one.
+	versioned_xcm: Option<u32>,
+}
+
+impl Metadata {
+	/// Reads SCALE encoded metadata, as returned by `state_getMetadata`, from `path`.
+	pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+		let contents = fs::read(path)?;
+		let metadata = match RuntimeMetadataPrefixed::decode(&mut contents.as_slice())?.1 {
+			RuntimeMetadata::V14(metadata) => metadata,
+			_ => return Err("only V14 metadata is supported".into()),
+		};
+		// `xcm::VersionedXcm`, or `staging_xcm::VersionedXcm` for some releases. Its instances
+		// only differ by the call type, which `Transact` keeps encoded.
+		let versioned_xcm = metadata
+			.types
+			.types
+			.iter()
+			.find(|ty| {
+				matches!(
+					ty.ty.path.segments.as_slice(),
+					[krate, name] if krate.ends_with("xcm") && name == "VersionedXcm"
+				)
+			})
+			.map(|ty| ty.id);
+
+		Ok(Self { metadata, versioned_xcm })
+	}
+
+	/// Decodes the whole of `data` as the type `type_id`.
+	fn decode(&self, mut data: &[u8], type_id: u32) -> Result<Value, Box<dyn Error>> {
+		let value = decode_as_type(&mut data, type_id, &self.metadata.types)
+			.map_err(|error| format!("{:?}", error))?;
+		match data.len() {
+			0 => Ok(value),
+			count => Err(format!("{} bytes left after decoding", count).into()),
+		}
+	}
+
+	/// Decodes `call`, encoded as the pallet index followed by the pallet's call enum, into the
+	/// pallet name and the call variant.
+	pub fn decode_call(&self, call: &[u8]) -> Result<(String, Variant<TypeId>), Box<dyn Error>> {
+		let (&pallet_index, data) = call.split_first().ok_or("empty call")?;
+		let pallet = self
+			.metadata
+			.pallets
+			.iter()
+			.find(|pallet| pallet.index == pallet_index)
+			.ok_or_else(|| format!("no pallet with index {}", pallet_index))?;
+		let calls = pallet.calls.as_ref().ok_or_else(|| format!("{} has no calls", pallet.name))?;
+
+		match self.decode(data, calls.ty.id)?.value {
+			ValueDef::Variant(variant) => Ok((pallet.name.clone(), variant)),
+			_ => Err(format!("the calls of {} are not an enum", pallet.name).into()),
+		}
+	}
+
+	/// Reads the candidates backed by `extrinsic`, none unless it is the parachains inherent.
+	pub fn candidates(&self, extrinsic: &[u8]) -> Vec<Candidate> {
+		let call = match extrinsic.split_first() {
+			Some((version, call)) if version & SIGNED_EXTRINSIC == 0 => call,
+			_ => return Vec::new(),
+		};
+		let data = match self.decode_call(call) {
+			Ok((pallet, variant)) if pallet == "ParaInherent" && variant.name == "enter" =>
+				variant.values,
+			_ => return Vec::new(),
+		};
+
+		composite_field(&data, "data")
+			.and_then(|data| field(data, "backed_candidates"))
+			.map(|candidates| items(candidates, is_struct))
+			.unwrap_or_default()
+			.into_iter()
+			.filter_map(|backed| candidate(field(backed, "candidate")?))
+			.collect()
+	}
+
+	/// Splits `data`, made of concatenated encoded `VersionedXcm`s, into the encoding of each.
+	pub fn split_xcm(&self, mut data: &[u8]) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+		let type_id = self.versioned_xcm.ok_or("the metadata has no VersionedXcm type")?;
+		let mut encodings = Vec::new();
+		while !data.is_empty() {
+			let before = data;
+			decode_as_type(&mut data, type_id, &self.metadata.types)
+				.map_err(|error| format!("{:?}", error))?;
+			encodings.push(before[..before.len() - data.len()].to_vec());
+		}
+		Ok(encodings)
+	}
+
+	/// Decodes an encoded `VersionedXcm` of any version the chain knows into a [`Value`].
+	pub fn decode_xcm_value(&self, data: &[u8]) -> Result<Value, Box<dyn Error>> {
+		let type_id = self.versioned_xcm.ok_or("the metadata has no VersionedXcm type")?;
+		self.decode(data, type_id)
+	}
+
+	/// Decodes an encoded `VersionedXcm` of any version the chain knows.
+	pub fn decode_xcm(&self, data: &[u8]) -> Result<Message, Box<dyn Error>> {
+		let variant = match self.decode_xcm_value(data)?.value {
+			ValueDef::Variant(variant) => variant,
+			_ => return Err("VersionedXcm is not an enum".into()),
+		};
+		let version = variant
+			.name
+			.strip_prefix('V')
+			.and_then(|version| version.parse().ok())
+			.ok_or_else(|| format!("unknown XCM version {}", variant.name))?;
+
+		let xcm = composite_values(&variant.values).first().copied().ok_or("empty message")?;
+		let instructions = match &xcm.value {
+			// Since v2, `Xcm` wraps a list of instructions.
+			ValueDef::Composite(_) =>
+				items(xcm, is_variant).into_iter().filter_map(instruction).collect(),
+			// Before, `Xcm` itself is an instruction carrying its effects.
+			ValueDef::Variant(_) => instruction(xcm).into_iter().collect(),
+			_ => return Err("unexpected XCM message shape".into()),
+		};
+
+		Ok(Message { version, instructions })
+	}
+}
+
+/// Reads a backed candidate's para and messages.
+fn candidate(candidate: &Value) -> Option<Candidate> {
+	let para_id = field(field(candidate, "descriptor")?, "para_id").and_then(as_u128)? as u32;
+	let commitments = field(candidate, "commitments")?;
+
+	let upward_messages = field(commitments, "upward_messages")
+		.map(|messages| items(messages, is_bytes))
+		.unwrap_or_default()
+		.into_iter()
+		.filter_map(as_bytes)
+		// An empty list in a wrapper looks like a wrapper around an empty message.
+		.filter(|message| !message.is_empty())
+		.collect();
+	let horizontal_messages = field(commitments, "horizontal_messages")
+		.map(|messages| items(messages, is_struct))
+		.unwrap_or_default()
+		.into_iter()
+		.filter_map(|message| {
+			let recipient = field(message, "recipient").and_then(as_u128)? as u32;
+			Some((recipient, field(message, "data").and_then(as_bytes)?))
+		})
+		.collect();
+
+	Some(Candidate { para_id, upward_messages, horizontal_messages })
+}
+
+/// Reads an instruction, or a pre-v2 message, from its variant.
+fn instruction(value: &Value) -> Option<Instruction> {
+	let variant = match &value.value {
+		ValueDef::Variant(variant) => variant,
+		_ => return None,
+	};
+	let mut instruction = Instruction {
+		name: variant.name.clone(),
+		operands: render_composite(&variant.values),
+		call: None,
+		decoded_call: None,
+		require_weight_at_most: None,
+	};
+	if variant.name == "Transact" {
+		instruction.call = composite_field(&variant.values, "call")
+			.and_then(|call| field(call, "encoded"))
+			.and_then(as_bytes);
+		// A plain number until v2, a `Weight` since v3.
+		instruction.require_weight_at_most =
+			composite_field(&variant.values, "require_weight_at_most")
+				.and_then(|weight| {
+					as_u128(weight).or_else(|| field(weight, "ref_time").and_then(as_u128))
+				})
+				.map(|weight| weight as u64);
+		// The call is shown separately.
+		if let Composite::Named(fields) = &variant.values {
+			let operands = fields.iter().filter(|(name, _)| name != "call").cloned().collect();
+			instruction.operands = render_composite(&Composite::Named(operands));
+		}
+	}
+	Some(instruction)
+}
+
+/// The fields of a composite, in order.
+pub fn composite_values<T>(composite: &Composite<T>) -> Vec<&scale_value::Value<T>> {
+	match composite {
+		Composite::Named(fields) => fields.iter().map(|(_, value)| value).collect(),
+		Composite::Unnamed(values) => values.iter().collect(),
+	}
+}
+
+/// The field `name` of a composite.
+pub fn composite_field<'a, T>(
+	composite: &'a Composite<T>,
+	name: &str,
+) -> Option<&'a scale_value::Value<T>> {
+	match composite {
+		Composite::Named(fields) =>
+			fields.iter().find(|(field, _)| field == name).map(|(_, value)| value),
+		// Looks through single field wrappers, e.g. `BackedCandidate(..)`.
+		Composite::Unnamed(values) if values.len() == 1 => field(&values[0], name),
+		Composite::Unnamed(_) => None,
+	}
+}
+
+/// The field `name` of a struct.
+pub fn field<'a, T>(
+	value: &'a scale_value::Value<T>,
+	name: &str,
+) -> Option<&'a scale_value::Value<T>> {
+	match &value.value {
+		ValueDef::Composite(composite) => composite_field(composite, name),
+		_ => None,
+	}
+}
+
+/// The items of a sequence of values satisfying `is_item`, looking through single field wrappers
+/// such as `BoundedVec`.
+pub fn items<T>(
+	value: &scale_value::Value<T>,
+	is_item: fn(&scale_value::Value<T>) -> bool,
+) -> Vec<&scale_value::Value<T>> {
+	match &value.value {
+		ValueDef::Composite(Composite::Unnamed(values)) if values.iter().all(is_item) =>
+			values.iter().collect(),
+		ValueDef::Composite(composite) => match composite_values(composite).as_slice() {
+			[inner] => items(inner, is_item),
+			_ => Vec::new(),
+		},
+		_ => Vec::new(),
+	}
+}
+
+pub fn is_struct<T>(value: &scale_value::Value<T>) -> bool {
+	matches!(value.value, ValueDef::Composite(Composite::Named(_)))
+}
+
+fn is_variant<T>(value: &scale_value::Value<T>) -> bool {
+	matches!(value.value, ValueDef::Variant(_))
+}
+
+fn is_byte<T>(value: &scale_value::Value<T>) -> bool {
+	matches!(value.value, ValueDef::Primitive(Primitive::U128(byte)) if byte <= u8::MAX.into())
+}
+
+fn is_bytes<T>(value: &scale_value::Value<T>) -> bool {
+	matches!(
+		&value.value,
+		ValueDef::Composite(Composite::Unnamed(values)) if values.iter().all(is_byte)
+	)
+}
+
+/// An unsigned number, looking through single field wrappers such as `ParaId`.
+pub fn as_u128<T>(value: &scale_value::Value<T>) -> Option<u128> {
+	match &value.value {
+		ValueDef::Primitive(Primitive::U128(number)) => Some(*number),
+		ValueDef::Composite(composite) => match composite_values(composite).as_slice() {
+			[inner] => as_u128(inner),
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+/// A byte sequence, looking through single field wrappers such as `H256`.
+pub fn as_bytes<T>(value: &scale_value::Value<T>) -> Option<Vec<u8>> {
+	match &value.value {
+		ValueDef::Composite(Composite::Unnamed(values)) if values.iter().all(is_byte) =>
+			values.iter().map(|byte| as_u128(byte).map(|byte| byte as u8)).collect(),
+		ValueDef::Composite(composite) => match composite_values(composite).as_slice() {
+			[inner] => as_bytes(inner),
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+/// Renders the fields of a variant like `Debug` would, with byte sequences in hex.
+fn render_composite<T>(composite: &Composite<T>) -> String {
+	let mut rendered = String::new();
+	match composite {
+		Composite::Named(fields) if !fields.is_empty() => {
+			let fields: Vec<_> = fields
+				.iter()
+				.map(|(name, value)| format!("{}: {}", name, describe(value)))
+				.collect();
+			let _ = write!(rendered, "{{ {} }}", fields.join(", "));
+		},
+		Composite::Unnamed(values) if !values.is_empty() => {
+			let values: Vec<_> = values.iter().map(describe).collect();
+			let _ = write!(rendered, "({})", values.join(", "));
+		},
+		_ => {},
+	}
+	rendered
+}
+
+/// Converts a value to JSON: structs become objects, sequences arrays (byte sequences hex
+/// strings) and variants either their name, or an object with their name as only key.
+pub fn to_json<T>(value: &scale_value::Value<T>) -> Json {
+	match &value.value {
+		ValueDef::Composite(composite) => composite_to_json(composite),
+		ValueDef::Variant(variant) if composite_values(&variant.values).is_empty() =>
+			Json::String(variant.name.clone()),
+		ValueDef::Variant(variant) => {
+			let values = match &variant.values {
+				Composite::Unnamed(values) if values.len() == 1 => to_json(&values[0]),
+				values => composite_to_json(values),
+			};
+			Json::Object([(variant.name.clone(), values)].into_iter().collect())
+		},
+		// Numbers beyond what JSON tools usually handle are kept as strings.
+		ValueDef::Primitive(Primitive::U128(number)) =>
+			u64::try_from(*number).map_or_else(|_| Json::String(number.to_string()), Json::from),
+		ValueDef::Primitive(Primitive::I128(number)) =>
+			i64::try_from(*number).map_or_else(|_| Json::String(number.to_string()), Json::from),
+		ValueDef::Primitive(Primitive::Bool(value)) => Json::Bool(*value),
+		ValueDef::Primitive(Primitive::String(value)) => Json::String(value.clone()),
+		ValueDef::Primitive(Primitive::Char(value)) => Json::String(value.to_string()),
+		ValueDef::Primitive(primitive) => Json::String(format!("{:?}", primitive)),
+		ValueDef::BitSequence(bits) => Json::String(format!("{:?}", bits)),
+	}
+}
+
+fn composite_to_json<T>(composite: &Composite<T>) -> Json {
+	match composite {
+		Composite::Named(fields) =>
+			fields.iter().map(|(name, value)| (name.clone(), to_json(value))).collect(),
+		Composite::Unnamed(values) if values.len() > 1 && values.iter().all(is_byte) => {
+			let bytes: Vec<u8> = values.iter().filter_map(as_u128).map(|byte| byte as u8).collect();
+			Json::String(format!("0x{}", hex::encode(bytes)))
+		},
+		// Looks through wrappers around sequences, such as `Xcm` or `MultiAssets`.
+		Composite::Unnamed(values) if values.len() == 1 && is_sequence(&values[0]) =>
+			to_json(&values[0]),
+		Composite::Unnamed(values) => values.iter().map(to_json).collect(),
+	}
+}
+
+fn is_sequence<T>(value: &scale_value::Value<T>) -> bool {
+	matches!(value.value, ValueDef::Composite(Composite::Unnamed(_)))
+}
+
+/// Renders a value like `Debug` would, with byte sequences in hex.
+pub fn describe<T>(value: &scale_value::Value<T>) -> String {
+	match &value.value {
+		ValueDef::Composite(Composite::Unnamed(values))
+			if values.len() > 1 && values.iter().all(is_byte) =>
+			format!("0x{}", hex::encode(as_bytes(value).unwrap_or_default())),
+		ValueDef::Composite(Composite::Unnamed(values)) => {
+			let values: Vec<_> = values.iter().map(describe).collect();
+			format!("[{}]", values.join(", "))
+		},
+		ValueDef::Composite(composite) => render_composite(composite),
+		ValueDef::Variant(variant) =>
+			format!("{}{}", variant.name, render_composite(&variant.values)),
+		ValueDef::Primitive(Primitive::U128(number)) => number.to_string(),
+		ValueDef::Primitive(Primitive::I128(number)) => number.to_string(),
+		ValueDef::Primitive(Primitive::Bool(value)) => value.to_string(),
+		ValueDef::Primitive(Primitive::String(value)) => format!("{:?}", value),
+		ValueDef::Primitive(primitive) => format!("{:?}", primitive),
+		ValueDef::BitSequence(bits) => format!("{:?}", bits),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	type V = scale_value::Value<()>;
+
+	fn unnamed(values: Vec<V>) -> V {
+		V::unnamed_composite(values)
+	}
+
+	fn named(fields: Vec<(&str, V)>) -> V {
+		V::named_composite(
+			fields
+				.into_iter()
+				.map(|(name, value)| (name.to_string(), value))
+				.collect::<Vec<_>>(),
+		)
+	}
+
+	#[test]
+	fn reads_through_wrappers() {
+		let para_id = unnamed(vec![V::u128(2000)]);
+		assert_eq!(as_u128(&para_id), Some(2000));
+
+		let hash = unnamed(vec![unnamed(vec![V::u128(1), V::u128(2), V::u128(255)])]);
+		assert_eq!(as_bytes(&hash), Some(vec![1, 2, 255]));
+		assert_eq!(as_bytes(&unnamed(vec![V::u128(256)])), None);
+
+		let wrapped = unnamed(vec![named(vec![("para_id", para_id)])]);
+		assert_eq!(field(&wrapped, "para_id").and_then(as_u128), Some(2000));
+		assert!(field(&wrapped, "recipient").is_none());
+	}
+
+	#[test]
+	fn tells_sequences_from_wrappers() {
+		let message = || unnamed(vec![V::u128(1), V::u128(2)]);
+		let messages = unnamed(vec![message()]);
+		assert_eq!(items(&messages, is_bytes).len(), 1);
+
+		let bounded = unnamed(vec![unnamed(vec![message(), message()])]);
+		assert_eq!(items(&bounded, is_bytes).len(), 2);
+
+		let candidates = unnamed(vec![named(vec![("para_id", V::u128(1))])]);
+		assert_eq!(items(&candidates, is_struct).len(), 1);
+	}
+
+	#[test]
+	fn converts_to_json() {
+		let asset = named(vec![
+			("id", V::unnamed_variant("Concrete", vec![named(vec![("parents", V::u128(1))])])),
+			("fun", V::unnamed_variant("Fungible", vec![V::u128(u128::MAX)])),
+		]);
+		let xcm = unnamed(vec![unnamed(vec![
+			V::unnamed_variant("ClearOrigin", Vec::new()),
+			V::unnamed_variant("WithdrawAsset", vec![unnamed(vec![unnamed(vec![asset])])]),
+			V::unnamed_variant("SetTopic", vec![unnamed(vec![V::u128(0xab), V::u128(0xcd)])]),
+		])]);
+
+		assert_eq!(
+			to_json(&V::unnamed_variant("V3", vec![xcm])),
+			serde_json::json!({ "V3": [
+				"ClearOrigin",
+				{ "WithdrawAsset": [{
+					"id": { "Concrete": { "parents": 1 } },
+					"fun": { "Fungible": u128::MAX.to_string() },
+				}] },
+				{ "SetTopic": "0xabcd" },
+			] })
+		);
+	}
+
+	#[test]
+	fn renders_like_debug() {
+		let value = named(vec![
+			("amount", V::u128(10)),
+			("id", unnamed(vec![V::u128(0xab), V::u128(0xcd)])),
+		]);
+		assert_eq!(describe(&value), "{ amount: 10, id: 0xabcd }");
+	}
+}
@@ -2,96 +2,416 @@
 // This file is dual-licensed as Apache-2.0 or GPL-3.0.
 // see LICENSE for license details.
 
-use subxt::{OnlineClient, PolkadotConfig};
+mod call;
+mod collect;
+mod diff;
+mod dynamic;
+mod message;
+mod processing;
+mod source;
+mod summary;
+
+use std::{
+	error::Error,
+	fs,
+	path::{Path, PathBuf},
+};
+
+use futures::{stream, StreamExt, TryStreamExt};
 
 // Generate the API from a static metadata path.
 #[subxt::subxt(
 	runtime_metadata_path = "./artifacts/polkadot_metadata.scale",
-	derive_for_all_types = "Eq, PartialEq"
+	derive_for_all_types = "Clone, Eq, PartialEq"
 )]
 pub mod polkadot {}
 
 use clap::Parser;
 #[derive(Parser, Default, Debug)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Arguments {
-	#[clap(short)]
-	para_id: u32,
-	#[clap(short)]
-	block_number: u32,
+	#[clap(subcommand)]
+	command: Option<Command>,
+	/// Paras sending the messages, or receiving them for downward messages, comma separated.
+	/// Messages of every para are inspected if omitted, except for downward messages.
+	#[clap(short, use_value_delimiter = true)]
+	para_id: Vec<u32>,
+	/// Block to inspect, or first block of the range to scan, required unless the block is read
+	/// from `--input`.
+	#[clap(short, required_unless_present = "input")]
+	block_number: Option<u32>,
+	/// Last block of the range to scan, inclusive.
+	#[clap(long, requires = "block_number")]
+	to: Option<u32>,
+	/// Maximum number of blocks requested from the node at once.
+	#[clap(long, default_value_t = 4)]
+	concurrency: usize,
+	/// Only print a table of the message counts per para.
+	#[clap(long)]
+	summary: bool,
+	/// Node to fetch the block from.
+	#[clap(long, default_value = source::DEFAULT_URL)]
+	url: String,
+	/// Read the block from a JSON or SCALE encoded dump instead of connecting to a node.
+	#[clap(long, conflicts_with = "block_number")]
+	input: Option<PathBuf>,
+	/// Kind of messages to inspect.
+	#[clap(long, arg_enum, default_value = "ump")]
+	channel: Channel,
+	/// Only show horizontal messages sent to this para.
+	#[clap(long)]
+	recipient: Option<u32>,
+	/// Number of relay blocks after the inspected ones to search for upward message processing
+	/// events.
+	#[clap(long, default_value_t = 2)]
+	lookahead: u32,
+	/// Flat weight assumed for every instruction, used to roughly estimate where a message failed.
+	/// Relays weigh each instruction differently, so the estimate is only a hint.
+	#[clap(long, default_value_t = 1_000_000_000)]
+	instruction_weight: u64,
+	/// SCALE encoded metadata of the relay, to decode its blocks and messages instead of the
+	/// bundled Polkadot metadata.
+	#[clap(long)]
+	metadata: Option<PathBuf>,
+	/// SCALE encoded metadata of the chain executing the messages, to decode `Transact` calls.
+	#[clap(long)]
+	call_metadata: Option<PathBuf>,
+	/// How to print the messages.
+	#[clap(long, arg_enum, default_value = "text")]
+	format: Format,
+	/// Write the encoded `VersionedXcm` of every message to this directory, to replay them.
+	#[clap(long)]
+	export: Option<PathBuf>,
+}
+
+/// Tools working on a single message rather than on blocks.
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+	/// Compare a sent message against the expected one, or a JSON template, value by value.
+	Diff {
+		/// The sent message: hex, or a file holding it encoded (e.g. from `--export`) or in hex.
+		sent: String,
+		/// The expected message, given like the sent one, or a `.json` template as printed by
+		/// `template`, where `"*"` matches any value.
+		expected: String,
+		/// SCALE encoded metadata of a chain, to decode XCM versions `staging-xcm` doesn't know.
+		#[clap(long)]
+		metadata: Option<PathBuf>,
+	},
+	/// Print the JSON form of a message, to edit into a template for `diff`.
+	Template {
+		/// The message, given like the ones compared by `diff`.
+		message: String,
+		/// SCALE encoded metadata of a chain, to decode XCM versions `staging-xcm` doesn't know.
+		#[clap(long)]
+		metadata: Option<PathBuf>,
+	},
+}
+
+/// The output format of the messages.
+#[derive(clap::ArgEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Format {
+	/// Human readable text.
+	#[default]
+	Text,
+	/// One JSON object per message and per line, see [`MessageOutput`].
+	Json,
 }
 
-type ParasInherentCall =
-	polkadot::runtime_types::polkadot_runtime_parachains::paras_inherent::pallet::Call;
-type RuntimeCall = polkadot::runtime_types::polkadot_runtime::RuntimeCall;
 type VersionedXcm = polkadot::runtime_types::xcm::VersionedXcm;
 
-use crate::polkadot::runtime_types::xcm::{
-	v2::{Instruction as InstructionV2, Xcm as XcmV2},
-	VersionedXcm::V2,
-};
+use collect::{Channel, Filter, RawMessage};
+use message::Message;
+use processing::Report;
+use serde::Serialize;
+
+/// A message printed with `--format json`.
+#[derive(Serialize)]
+struct MessageOutput<'a> {
+	/// The relay block the message was found in, unknown for blocks read from a dump.
+	block_number: Option<u32>,
+	para_id: Option<u32>,
+	channel: Channel,
+	sender: Option<u32>,
+	recipient: Option<u32>,
+	/// The position of the message among the ones sent over the same channel, from 0.
+	index: u32,
+	#[serde(flatten)]
+	message: &'a Message,
+	processing: Vec<ProcessingOutput>,
+}
+
+/// A relay-side processing event of a message printed with `--format json`.
+#[derive(Serialize)]
+struct ProcessingOutput {
+	block_number: u32,
+	outcome: String,
+	/// A rough estimate of the index of the instruction an incomplete execution stopped at, see
+	/// [`processing::failed_instruction`].
+	failed_instruction_estimate: Option<usize>,
+}
 
-use polkadot::runtime_types::polkadot_parachain::primitives::Id as ParaId;
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-	let args = Arguments::parse();
+async fn main() -> Result<(), Box<dyn Error>> {
+	let mut args = Arguments::parse();
 	tracing_subscriber::fmt::init();
 
-	// Create a client to use:
-	let api = OnlineClient::<PolkadotConfig>::from_url("wss://rpc.polkadot.io:443").await?;
-
-	let block_number = args.block_number;
-
-	let block_hash = api
-		.rpc()
-		.block_hash(Some(block_number.into()))
-		.await?
-		.expect("block number not found");
-
-	let block = api.clone().rpc().block(Some(block_hash)).await?.expect("block not found");
-
-	for extrinsic in block.block.extrinsics {
-		let mut sliced = &extrinsic.0[1..];
-
-		let decoded: Result<RuntimeCall, codec::Error> = codec::Decode::decode(&mut sliced);
-		match decoded {
-			Ok(RuntimeCall::ParaInherent(ParasInherentCall::enter { data })) => {
-				for candidate in data.backed_candidates {
-					if candidate.candidate.descriptor.para_id == ParaId(args.para_id) {
-						for (index, upward_message) in
-							candidate.candidate.commitments.upward_messages.iter().enumerate()
-						{
-							let xcm: VersionedXcm =
-								codec::Decode::decode(&mut upward_message.as_slice())
-									.expect("unable to decode");
-							let instructions = match xcm {
-								V2(XcmV2(instructions)) => instructions,
-								_ => panic!("Only v2 supported"),
-							};
-							pretty_print_xcm(index as u32, instructions);
-						}
+	if let Some(command) = args.command.take() {
+		return run_command(command)
+	}
+
+	let metadata = args.metadata.as_deref().map(dynamic::Metadata::from_file).transpose()?;
+	let metadata = metadata.as_ref();
+	let call_decoder =
+		args.call_metadata.as_deref().map(call::CallDecoder::from_file).transpose()?;
+	if let Some(directory) = &args.export {
+		fs::create_dir_all(directory)?;
+	}
+	let filter = Filter { senders: args.para_id.clone(), recipient: args.recipient };
+
+	let (api, blocks) = match args.input {
+		Some(ref path) => {
+			let block = source::read_block(path)?;
+			let messages = collect_messages(None, metadata, &block, &args, &filter).await?;
+			(None, vec![(None, messages)])
+		},
+		None => {
+			let first = args.block_number.ok_or("a block number is required")?;
+			let last = args.to.unwrap_or(first);
+			if last < first {
+				return Err("the range ends before it starts".into())
+			}
+			// Create a client to use:
+			let api = source::Client::from_url(&args.url).await?;
+			let blocks: Vec<_> = stream::iter(first..=last)
+				.map(|block_number| {
+					let (api, args, filter) = (&api, &args, &filter);
+					async move {
+						let block = source::fetch_block(api, block_number).await?;
+						let messages =
+							collect_messages(Some(api), metadata, &block, args, filter).await?;
+						Ok::<_, Box<dyn Error>>((Some(block_number), messages))
 					}
-				}
-			},
-			_ => continue,
+				})
+				.buffered(args.concurrency.max(1))
+				.try_collect()
+				.await?;
+			(Some(api), blocks)
+		},
+	};
+
+	// Upward messages are processed by the relay in the blocks following the one they are backed
+	// in, which only a node can tell.
+	let reports = match (&api, args.channel, args.block_number) {
+		(Some(api), Channel::Ump, Some(first)) if args.lookahead > 0 => {
+			let last = args.to.unwrap_or(first);
+			let block_numbers = first + 1..=last + args.lookahead;
+			processing::fetch_reports(api, block_numbers, args.concurrency).await?
+		},
+		_ => Default::default(),
+	};
+
+	let mut summary = summary::Summary::default();
+	for (block_number, messages) in blocks {
+		for raw in messages {
+			let reports = reports
+				.get(&processing::message_id(&raw.data))
+				.map(Vec::as_slice)
+				.unwrap_or_default();
+			if let Some(directory) = &args.export {
+				fs::write(directory.join(export_file_name(block_number, &raw)), &raw.data)?;
+			}
+			let message = match metadata {
+				Some(metadata) => metadata.decode_xcm(&raw.data),
+				None => message::decode(&raw.data).map_err(Into::into),
+			};
+			if args.summary {
+				summary.record(raw.para_id().unwrap_or_default(), message.is_ok(), reports);
+				continue
+			}
+			let mut message = match message {
+				Ok(message) => message,
+				Err(error) => {
+					eprintln!(
+						"unable to decode message {} of block {:?}: {}",
+						raw.index + 1,
+						block_number,
+						error
+					);
+					continue
+				},
+			};
+
+			if let Some(call_decoder) = &call_decoder {
+				message.decode_calls(call_decoder);
+			}
+
+			match args.format {
+				Format::Text => {
+					pretty_print_xcm(block_number, &raw, &message);
+					for report in reports {
+						print_report(report, &message, args.instruction_weight);
+					}
+				},
+				Format::Json => {
+					let output = MessageOutput {
+						block_number,
+						para_id: raw.para_id(),
+						channel: raw.channel,
+						sender: raw.sender,
+						recipient: raw.recipient,
+						index: raw.index,
+						message: &message,
+						processing: reports
+							.iter()
+							.map(|report| ProcessingOutput {
+								block_number: report.block_number,
+								outcome: format!("{:?}", report.processing),
+								failed_instruction_estimate: report.incomplete_weight().and_then(
+									|weight_used| {
+										processing::failed_instruction(
+											&message,
+											weight_used,
+											args.instruction_weight,
+										)
+									},
+								),
+							})
+							.collect(),
+					};
+					println!("{}", serde_json::to_string(&output)?);
+				},
+			}
 		}
 	}
+
+	if args.summary {
+		print!("{}", summary);
+	}
+	Ok(())
+}
+
+fn run_command(command: Command) -> Result<(), Box<dyn Error>> {
+	match command {
+		Command::Diff { sent, expected, metadata } => {
+			let metadata = metadata.as_deref().map(dynamic::Metadata::from_file).transpose()?;
+			let sent = message_json(&sent, metadata.as_ref())?;
+			let expected = match Path::new(&expected).extension() {
+				Some(extension) if extension == "json" =>
+					serde_json::from_slice(&fs::read(&expected)?)?,
+				_ => message_json(&expected, metadata.as_ref())?,
+			};
+
+			let differences = diff::diff(&sent, &expected);
+			if differences.is_empty() {
+				println!("The messages match");
+			}
+			for difference in differences {
+				println!("{}", difference);
+			}
+		},
+		Command::Template { message, metadata } => {
+			let metadata = metadata.as_deref().map(dynamic::Metadata::from_file).transpose()?;
+			let message = message_json(&message, metadata.as_ref())?;
+			println!("{}", serde_json::to_string_pretty(&message)?);
+		},
+	}
 	Ok(())
 }
 
-fn pretty_print_xcm(index: u32, instructions: Vec<InstructionV2>) {
-	println!("Xcm message number {:?}", index + 1);
-
-	for instruction in instructions {
-		match instruction {
-			InstructionV2::Transact { origin_type, require_weight_at_most, call } => {
-				println!(
-					"{{ origin_type {:?}, require_weight_at_most: {:?}, call: {:?} }}",
-					origin_type,
-					require_weight_at_most,
-					hex::encode(call.encoded)
-				)
-			},
-			_ => println!("{:?}", instruction),
+/// Reads a message given as hex, or as a file holding it encoded or in hex, into its JSON form.
+fn message_json(
+	input: &str,
+	metadata: Option<&dynamic::Metadata>,
+) -> Result<serde_json::Value, Box<dyn Error>> {
+	let data = match fs::read(input) {
+		Ok(contents) => std::str::from_utf8(&contents)
+			.ok()
+			.and_then(|text| xcm_decoder::from_hex(text).ok())
+			.unwrap_or(contents),
+		Err(_) => xcm_decoder::from_hex(input)?,
+	};
+	Ok(match metadata {
+		Some(metadata) => dynamic::to_json(&metadata.decode_xcm_value(&data)?),
+		None => dynamic::to_json(&xcm_decoder::versioned_xcm_value(&data)?),
+	})
+}
+
+/// Collects the messages of `block` sent over the inspected channel.
+async fn collect_messages(
+	api: Option<&source::Client>,
+	metadata: Option<&dynamic::Metadata>,
+	block: &source::Block,
+	args: &Arguments,
+	filter: &Filter,
+) -> Result<Vec<RawMessage>, Box<dyn Error>> {
+	match args.channel {
+		Channel::Dmp => {
+			let (api, block_hash) = api
+				.zip(block.hash)
+				.ok_or("downward messages are read from storage and need a node")?;
+			if args.para_id.is_empty() {
+				return Err("downward messages need the paras to inspect, given with -p".into())
+			}
+			let mut messages = Vec::new();
+			for &para_id in &args.para_id {
+				let queue = source::fetch_downward_messages(api, block_hash, para_id).await?;
+				messages.extend(collect::from_downward_queue(para_id, queue));
+			}
+			Ok(messages)
+		},
+		channel => Ok(match metadata {
+			Some(metadata) => collect::from_candidates(
+				block.extrinsics.iter().flat_map(|extrinsic| metadata.candidates(extrinsic)),
+				channel,
+				filter,
+				|data| metadata.split_xcm(data),
+			),
+			None => collect::from_extrinsics(&block.extrinsics, channel, filter),
+		}),
+	}
+}
+
+/// Names the file a message is exported to, e.g. `10946379-ump-2012-0.scale`.
+fn export_file_name(block_number: Option<u32>, raw: &RawMessage) -> String {
+	let block = block_number.map_or_else(|| "input".into(), |number| number.to_string());
+	let para_id = raw.para_id().map_or_else(|| "relay".into(), |id| id.to_string());
+	let channel = format!("{:?}", raw.channel).to_lowercase();
+	format!("{}-{}-{}-{}.scale", block, channel, para_id, raw.index)
+}
+
+fn print_report(report: &Report, message: &Message, instruction_weight: u64) {
+	println!("  {}", report);
+	if let Some(weight_used) = report.incomplete_weight() {
+		match processing::failed_instruction(message, weight_used, instruction_weight) {
+			Some(index) => println!(
+				"  likely failed around instruction {} ({}), a rough estimate from the weight used",
+				index + 1,
+				message.instructions[index].name
+			),
+			None => println!("  unable to estimate the failed instruction from the weight used"),
 		}
 	}
 }
+
+fn pretty_print_xcm(block_number: Option<u32>, raw: &RawMessage, message: &Message) {
+	let route = match (raw.sender, raw.recipient) {
+		(Some(sender), Some(recipient)) => format!("para {} -> para {}", sender, recipient),
+		(Some(sender), None) => format!("para {} -> relay", sender),
+		(None, Some(recipient)) => format!("relay -> para {}", recipient),
+		(None, None) => "relay".into(),
+	};
+	let block = block_number.map(|number| format!(" in block {}", number)).unwrap_or_default();
+	println!(
+		"Xcm message number {:?}{} ({:?} {}, v{})",
+		raw.index + 1,
+		block,
+		raw.channel,
+		route,
+		message.version
+	);
+
+	for instruction in &message.instructions {
+		println!("{}", instruction);
+	}
+}
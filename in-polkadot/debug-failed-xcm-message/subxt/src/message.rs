@@ -0,0 +1,315 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Version independent representation of decoded XCM messages.
+
+use std::fmt;
+
+use serde::{Serialize, Serializer};
+
+use crate::{
+	call::{CallDecoder, DecodedCall},
+	polkadot::runtime_types::xcm::{v0, v1, v2, VersionedXcm},
+};
+
+/// An XCM instruction, independent of the version it was decoded from.
+///
+/// XCM v0 and v1 have no instructions: their messages carry the effects to apply on the
+/// holding register. Each of those messages becomes a single instruction here.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Instruction {
+	/// The name of the instruction, e.g. `WithdrawAsset`.
+	pub name: String,
+	/// The operands of the instruction, as printed by `Debug`.
+	pub operands: String,
+	/// The encoded call of a `Transact`.
+	#[serde(serialize_with = "serialize_hex", skip_serializing_if = "Option::is_none")]
+	pub call: Option<Vec<u8>>,
+	/// The call of a `Transact`, once decoded with [`Message::decode_calls`].
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub decoded_call: Option<DecodedCall>,
+	/// The weight a `Transact` requires for its call.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub require_weight_at_most: Option<u64>,
+}
+
+fn serialize_hex<S: Serializer>(data: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+	match data {
+		Some(data) => serializer.serialize_str(&format!("0x{}", hex::encode(data))),
+		None => serializer.serialize_none(),
+	}
+}
+
+impl Instruction {
+	/// Splits the `Debug` output of an instruction into its name and operands.
+	fn from_debug(instruction: &impl fmt::Debug) -> Self {
+		let formatted = format!("{:?}", instruction);
+		let (name, operands) =
+			formatted.split_at(formatted.find(['(', ' ', '{']).unwrap_or(formatted.len()));
+		Self {
+			name: name.into(),
+			operands: operands.trim().into(),
+			call: None,
+			decoded_call: None,
+			require_weight_at_most: None,
+		}
+	}
+
+	fn transact(origin_type: impl fmt::Debug, require_weight_at_most: u64, call: Vec<u8>) -> Self {
+		Self {
+			name: "Transact".into(),
+			operands: format!(
+				"{{ origin_type: {:?}, require_weight_at_most: {:?} }}",
+				origin_type, require_weight_at_most
+			),
+			call: Some(call),
+			decoded_call: None,
+			require_weight_at_most: Some(require_weight_at_most),
+		}
+	}
+
+	fn relayed_from(who: impl fmt::Debug) -> Self {
+		Self {
+			name: "RelayedFrom".into(),
+			operands: format!("{{ who: {:?} }}", who),
+			call: None,
+			decoded_call: None,
+			require_weight_at_most: None,
+		}
+	}
+}
+
+impl fmt::Display for Instruction {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.name)?;
+		if !self.operands.is_empty() {
+			write!(f, " {}", self.operands)?;
+		}
+		match (&self.decoded_call, &self.call) {
+			(Some(decoded_call), _) => write!(f, " call: {}", decoded_call)?,
+			(None, Some(call)) => write!(f, " call: 0x{}", hex::encode(call))?,
+			(None, None) => (),
+		}
+		Ok(())
+	}
+}
+
+/// A decoded XCM message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Message {
+	/// The XCM version the message was encoded with.
+	pub version: u32,
+	pub instructions: Vec<Instruction>,
+}
+
+impl Message {
+	/// Decodes the calls of the message's `Transact` instructions with `decoder`.
+	///
+	/// Calls that fail to decode are kept encoded, the error is reported on stderr.
+	pub fn decode_calls(&mut self, decoder: &CallDecoder) {
+		for instruction in &mut self.instructions {
+			if let Some(call) = &instruction.call {
+				match decoder.decode(call) {
+					Ok(decoded_call) => instruction.decoded_call = Some(decoded_call),
+					Err(error) =>
+						eprintln!("unable to decode the call of {}: {}", instruction, error),
+				}
+			}
+		}
+	}
+}
+
+/// Decodes an encoded `VersionedXcm` with the bundled metadata, or with `staging-xcm` for the
+/// versions the bundled metadata does not know.
+pub fn decode(data: &[u8]) -> Result<Message, xcm_decoder::Error> {
+	match xcm_decoder::decode::<VersionedXcm>(data) {
+		Ok(xcm) => Ok(xcm.into()),
+		Err(_) => xcm_decoder::versioned_xcm(data).map(Into::into),
+	}
+}
+
+impl From<VersionedXcm> for Message {
+	fn from(xcm: VersionedXcm) -> Self {
+		match xcm {
+			VersionedXcm::V0(message) => Message { version: 0, instructions: from_v0(message) },
+			VersionedXcm::V1(message) => Message { version: 1, instructions: from_v1(message) },
+			VersionedXcm::V2(message) => Message { version: 2, instructions: from_v2(message) },
+		}
+	}
+}
+
+impl From<xcm::VersionedXcm<()>> for Message {
+	fn from(xcm: xcm::VersionedXcm<()>) -> Self {
+		match xcm {
+			xcm::VersionedXcm::V2(message) =>
+				Message { version: 2, instructions: from_staging_v2(message) },
+			xcm::VersionedXcm::V3(message) =>
+				Message { version: 3, instructions: from_staging_v3(message) },
+		}
+	}
+}
+
+fn from_v0(message: v0::Xcm) -> Vec<Instruction> {
+	match message {
+		v0::Xcm::Transact { origin_type, require_weight_at_most, call } =>
+			vec![Instruction::transact(origin_type, require_weight_at_most, call.encoded)],
+		v0::Xcm::RelayedFrom { who, message } => {
+			let mut instructions = vec![Instruction::relayed_from(who)];
+			instructions.extend(from_v0(*message));
+			instructions
+		},
+		message => vec![Instruction::from_debug(&message)],
+	}
+}
+
+fn from_v1(message: v1::Xcm) -> Vec<Instruction> {
+	match message {
+		v1::Xcm::Transact { origin_type, require_weight_at_most, call } =>
+			vec![Instruction::transact(origin_type, require_weight_at_most, call.encoded)],
+		v1::Xcm::RelayedFrom { who, message } => {
+			let mut instructions = vec![Instruction::relayed_from(who)];
+			instructions.extend(from_v1(*message));
+			instructions
+		},
+		message => vec![Instruction::from_debug(&message)],
+	}
+}
+
+fn from_v2(v2::Xcm(instructions): v2::Xcm) -> Vec<Instruction> {
+	instructions
+		.into_iter()
+		.map(|instruction| match instruction {
+			v2::Instruction::Transact { origin_type, require_weight_at_most, call } =>
+				Instruction::transact(origin_type, require_weight_at_most, call.encoded),
+			instruction => Instruction::from_debug(&instruction),
+		})
+		.collect()
+}
+
+fn from_staging_v2(xcm::v2::Xcm(instructions): xcm::v2::Xcm<()>) -> Vec<Instruction> {
+	instructions
+		.into_iter()
+		.map(|instruction| match instruction {
+			xcm::v2::Instruction::Transact { origin_type, require_weight_at_most, call } =>
+				Instruction::transact(origin_type, require_weight_at_most, call.into_encoded()),
+			instruction => Instruction::from_debug(&instruction),
+		})
+		.collect()
+}
+
+/// Since v3, the weight of a `Transact` has a proof size: only its ref time is kept.
+fn from_staging_v3(xcm::v3::Xcm(instructions): xcm::v3::Xcm<()>) -> Vec<Instruction> {
+	instructions
+		.into_iter()
+		.map(|instruction| match instruction {
+			xcm::v3::Instruction::Transact { origin_kind, require_weight_at_most, call } =>
+				Instruction::transact(
+					origin_kind,
+					require_weight_at_most.ref_time(),
+					call.into_encoded(),
+				),
+			instruction => Instruction::from_debug(&instruction),
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::polkadot::runtime_types::xcm::{double_encoded::DoubleEncoded, v0::OriginKind};
+
+	#[test]
+	fn splits_debug_output_into_name_and_operands() {
+		let instruction = Instruction::from_debug(&v2::Instruction::ClearOrigin);
+		assert_eq!(instruction.name, "ClearOrigin");
+		assert_eq!(instruction.operands, "");
+
+		let instruction = Instruction::from_debug(&v2::Instruction::RefundSurplus);
+		assert_eq!(instruction.to_string(), "RefundSurplus");
+	}
+
+	#[test]
+	fn normalises_transact_of_every_version() {
+		let call = DoubleEncoded { encoded: vec![0, 1, 2] };
+		let expected = Instruction::transact(OriginKind::Native, 1_000, vec![0, 1, 2]);
+
+		let v0 = VersionedXcm::V0(v0::Xcm::Transact {
+			origin_type: OriginKind::Native,
+			require_weight_at_most: 1_000,
+			call: call.clone(),
+		});
+		let v1 = VersionedXcm::V1(v1::Xcm::Transact {
+			origin_type: OriginKind::Native,
+			require_weight_at_most: 1_000,
+			call: call.clone(),
+		});
+		let v2 = VersionedXcm::V2(v2::Xcm(vec![v2::Instruction::Transact {
+			origin_type: OriginKind::Native,
+			require_weight_at_most: 1_000,
+			call,
+		}]));
+
+		for (version, xcm) in [v0, v1, v2].into_iter().enumerate() {
+			let message = Message::from(xcm);
+			assert_eq!(message.version, version as u32);
+			assert_eq!(message.instructions, vec![expected.clone()]);
+		}
+		assert_eq!(
+			expected.to_string(),
+			"Transact { origin_type: Native, require_weight_at_most: 1000 } call: 0x000102"
+		);
+	}
+
+	#[test]
+	fn falls_back_on_staging_xcm_for_v3() {
+		use codec::Encode;
+		use xcm::v3::{Instruction::*, OriginKind, Weight};
+
+		let xcm = xcm::VersionedXcm::<()>::V3(xcm::v3::Xcm(vec![
+			ClearOrigin,
+			Transact {
+				origin_kind: OriginKind::Native,
+				require_weight_at_most: Weight::from_parts(1_000, 2_000),
+				call: vec![0, 1, 2].into(),
+			},
+		]));
+
+		let message = decode(&xcm.encode()).unwrap();
+		assert_eq!(message.version, 3);
+		assert_eq!(message.instructions[0].name, "ClearOrigin");
+		assert_eq!(
+			message.instructions[1],
+			Instruction::transact(OriginKind::Native, 1_000, vec![0, 1, 2])
+		);
+		assert!(decode(&[3]).is_err());
+	}
+
+	#[test]
+	fn serializes_to_a_stable_schema() {
+		let message = Message::from(VersionedXcm::V2(v2::Xcm(vec![
+			v2::Instruction::ClearOrigin,
+			v2::Instruction::Transact {
+				origin_type: OriginKind::Native,
+				require_weight_at_most: 1_000,
+				call: DoubleEncoded { encoded: vec![0, 1, 2] },
+			},
+		])));
+
+		assert_eq!(
+			serde_json::to_value(&message).unwrap(),
+			serde_json::json!({
+				"version": 2,
+				"instructions": [
+					{ "name": "ClearOrigin", "operands": "" },
+					{
+						"name": "Transact",
+						"operands": "{ origin_type: Native, require_weight_at_most: 1000 }",
+						"call": "0x000102",
+						"require_weight_at_most": 1000,
+					},
+				],
+			})
+		);
+	}
+}
@@ -0,0 +1,287 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Finds how the relay processed upward messages, from the events of the blocks following the
+//! one the messages were sent in.
+//!
+//! Events are read by name, so both relays processing upward messages with the `Ump` pallet and
+//! those using the `MessageQueue` pallet are supported, whatever their metadata.
+
+use std::{collections::HashMap, error::Error, fmt, ops::RangeInclusive};
+
+use futures::{stream, StreamExt};
+use subxt::ext::{
+	scale_value::{scale::TypeId, Composite, Primitive, Value, ValueDef},
+	sp_core::hashing::blake2_256,
+};
+
+use crate::{
+	dynamic::{as_bytes, as_u128, composite_field, composite_values, describe, field},
+	message::Message,
+	polkadot::runtime_types::xcm::v2::traits::Outcome,
+	source::Client,
+};
+
+/// The id the relay identifies an upward message with.
+pub type MessageId = [u8; 32];
+
+/// Computes the id of the upward message encoded as `data`.
+pub fn message_id(data: &[u8]) -> MessageId {
+	blake2_256(data)
+}
+
+/// What the relay did with an upward message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Processing {
+	/// The message was executed with the given outcome.
+	Executed(Outcome),
+	/// The message was processed, for outcomes that do not decode as an XCM v2 `Outcome`.
+	Processed { success: bool, weight_used: u64, error: Option<String> },
+	/// The message queue gave up on the message.
+	ProcessingFailed { error: String },
+	/// The message could not be decoded.
+	InvalidFormat,
+	/// The message uses an XCM version the relay does not support.
+	UnsupportedVersion,
+	/// There was not enough weight left in the block, the message will be retried.
+	WeightExhausted { remaining: u64, required: u64 },
+	/// The message needs more weight than a single message may use and was set aside.
+	OverweightEnqueued { index: u64, required: u64 },
+}
+
+/// A processing event of an upward message, with the relay block it was found in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+	pub block_number: u32,
+	pub processing: Processing,
+}
+
+impl Report {
+	/// Whether the relay gave up on the message, rather than executing it or retrying later.
+	pub fn is_failure(&self) -> bool {
+		!matches!(
+			self.processing,
+			Processing::Executed(Outcome::Complete(_)) |
+				Processing::Processed { success: true, .. } |
+				Processing::WeightExhausted { .. }
+		)
+	}
+
+	/// The weight used by an execution that stopped at an error, `None` for other outcomes.
+	pub fn incomplete_weight(&self) -> Option<u64> {
+		match self.processing {
+			Processing::Executed(Outcome::Incomplete(weight_used, _)) => Some(weight_used),
+			Processing::Processed { success: false, weight_used, .. } if weight_used > 0 =>
+				Some(weight_used),
+			_ => None,
+		}
+	}
+}
+
+impl fmt::Display for Report {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "processed in block {}: ", self.block_number)?;
+		match &self.processing {
+			Processing::Executed(Outcome::Complete(weight)) =>
+				write!(f, "complete, using {} weight", weight),
+			Processing::Executed(Outcome::Incomplete(weight, error)) =>
+				write!(f, "incomplete with {:?}, using {} weight", error, weight),
+			Processing::Executed(Outcome::Error(error)) =>
+				write!(f, "failed before execution with {:?}", error),
+			Processing::Processed { success: true, weight_used, .. } =>
+				write!(f, "complete, using {} weight", weight_used),
+			Processing::Processed { success: false, weight_used, error } => write!(
+				f,
+				"failed with {}, using {} weight",
+				error.as_deref().unwrap_or("an unknown error"),
+				weight_used
+			),
+			Processing::ProcessingFailed { error } => write!(f, "processing failed with {}", error),
+			processing => write!(f, "{:?}", processing),
+		}
+	}
+}
+
+/// Collects the upward message processing events of the relay blocks in `block_numbers`,
+/// grouped by message id, fetching up to `concurrency` blocks at once.
+pub async fn fetch_reports(
+	api: &Client,
+	block_numbers: RangeInclusive<u32>,
+	concurrency: usize,
+) -> Result<HashMap<MessageId, Vec<Report>>, Box<dyn Error>> {
+	let mut blocks = stream::iter(block_numbers)
+		.map(|block_number| fetch_block_reports(api, block_number))
+		.buffered(concurrency.max(1));
+
+	let mut reports: HashMap<MessageId, Vec<Report>> = HashMap::new();
+	while let Some(block_reports) = blocks.next().await {
+		for (id, report) in block_reports? {
+			reports.entry(id).or_default().push(report);
+		}
+	}
+	Ok(reports)
+}
+
+/// Collects the upward message processing events of the relay block `block_number`, none if
+/// the chain has not reached it yet.
+async fn fetch_block_reports(
+	api: &Client,
+	block_number: u32,
+) -> Result<Vec<(MessageId, Report)>, Box<dyn Error>> {
+	let block_hash = match api.rpc().block_hash(Some(block_number.into())).await? {
+		Some(block_hash) => block_hash,
+		None => return Ok(Vec::new()),
+	};
+	let events = api.events().at(Some(block_hash)).await?;
+
+	let mut reports = Vec::new();
+	for event in events.iter() {
+		let event = event?;
+		let processing = match (event.pallet_name(), event.variant_name()) {
+			("Ump", "ExecutedUpward") =>
+				match xcm_decoder::decode::<(MessageId, Outcome)>(event.field_bytes()) {
+					Ok((id, outcome)) => Some((id, Processing::Executed(outcome))),
+					// Relays that moved to XCM v3 report a v3 `Outcome`.
+					Err(_) => executed_upward(&event.field_values()?),
+				},
+			("Ump", "InvalidFormat") =>
+				Some((xcm_decoder::decode(event.field_bytes())?, Processing::InvalidFormat)),
+			("Ump", "UnsupportedVersion") =>
+				Some((xcm_decoder::decode(event.field_bytes())?, Processing::UnsupportedVersion)),
+			("Ump", "WeightExhausted") => {
+				let fields = event.field_values()?;
+				let values = composite_values(&fields);
+				Some((
+					message_id_of(values.first().copied())?,
+					Processing::WeightExhausted {
+						remaining: values.get(1).copied().and_then(weight).unwrap_or_default(),
+						required: values.get(2).copied().and_then(weight).unwrap_or_default(),
+					},
+				))
+			},
+			("Ump", "OverweightEnqueued") => {
+				let fields = event.field_values()?;
+				let values = composite_values(&fields);
+				Some((
+					message_id_of(values.get(1).copied())?,
+					Processing::OverweightEnqueued {
+						index: values.get(2).copied().and_then(as_u128).unwrap_or_default() as u64,
+						required: values.get(3).copied().and_then(weight).unwrap_or_default(),
+					},
+				))
+			},
+			("MessageQueue", "Processed") => {
+				let fields = event.field_values()?;
+				let success = matches!(
+					composite_field(&fields, "success").map(|success| &success.value),
+					Some(ValueDef::Primitive(Primitive::Bool(true)))
+				);
+				Some((
+					message_id_of(composite_field(&fields, "id"))?,
+					Processing::Processed {
+						success,
+						weight_used: composite_field(&fields, "weight_used")
+							.and_then(weight)
+							.unwrap_or_default(),
+						error: None,
+					},
+				))
+			},
+			("MessageQueue", "ProcessingFailed") => {
+				let fields = event.field_values()?;
+				Some((
+					message_id_of(composite_field(&fields, "id"))?,
+					Processing::ProcessingFailed {
+						error: composite_field(&fields, "error").map(describe).unwrap_or_default(),
+					},
+				))
+			},
+			_ => None,
+		};
+		if let Some((id, processing)) = processing {
+			reports.push((id, Report { block_number, processing }));
+		}
+	}
+
+	Ok(reports)
+}
+
+/// Reads an `ExecutedUpward` event whose outcome is not an XCM v2 `Outcome`.
+fn executed_upward(fields: &Composite<TypeId>) -> Option<(MessageId, Processing)> {
+	let values = composite_values(fields);
+	let id = message_id_of(values.first().copied()).ok()?;
+	let outcome = match &values.get(1)?.value {
+		ValueDef::Variant(outcome) => outcome,
+		_ => return None,
+	};
+	let outcome_values = composite_values(&outcome.values);
+	let (success, weight_used, error) = match outcome.name.as_str() {
+		"Complete" => (true, outcome_values.first().copied().and_then(weight), None),
+		"Incomplete" => (
+			false,
+			outcome_values.first().copied().and_then(weight),
+			outcome_values.get(1).copied().map(describe),
+		),
+		_ => (false, None, outcome_values.first().copied().map(describe)),
+	};
+	Some((
+		id,
+		Processing::Processed { success, weight_used: weight_used.unwrap_or_default(), error },
+	))
+}
+
+/// Reads a message id, a `[u8; 32]` or an `H256`.
+fn message_id_of(value: Option<&Value<TypeId>>) -> Result<MessageId, Box<dyn Error>> {
+	value
+		.and_then(as_bytes)
+		.and_then(|bytes| MessageId::try_from(bytes).ok())
+		.ok_or_else(|| "unexpected message id".into())
+}
+
+/// Reads a weight, a plain number or the `ref_time` of a `Weight`.
+fn weight(value: &Value<TypeId>) -> Option<u64> {
+	as_u128(value)
+		.or_else(|| field(value, "ref_time").and_then(as_u128))
+		.map(|weight| weight as u64)
+}
+
+/// Roughly estimates the index of the instruction an incomplete execution stopped at.
+///
+/// Outcomes only carry the weight used, which covers every instruction up to and including the
+/// failing one. The estimate assumes each instruction weighs the same `instruction_weight`, plus
+/// the weight required by its call for a `Transact`. Relays weigh each instruction from its own
+/// benchmark instead, so the index is only a hint of where to look.
+pub fn failed_instruction(
+	message: &Message,
+	weight_used: u64,
+	instruction_weight: u64,
+) -> Option<usize> {
+	let mut weight = 0u64;
+	message.instructions.iter().position(|instruction| {
+		weight = weight
+			.saturating_add(instruction_weight)
+			.saturating_add(instruction.require_weight_at_most.unwrap_or_default());
+		weight >= weight_used
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::polkadot::runtime_types::xcm::{v2, VersionedXcm};
+
+	#[test]
+	fn estimates_the_failed_instruction() {
+		let message = Message::from(VersionedXcm::V2(v2::Xcm(vec![
+			v2::Instruction::ClearOrigin,
+			v2::Instruction::RefundSurplus,
+			v2::Instruction::ClearError,
+		])));
+
+		assert_eq!(failed_instruction(&message, 1_000, 1_000), Some(0));
+		assert_eq!(failed_instruction(&message, 2_000, 1_000), Some(1));
+		assert_eq!(failed_instruction(&message, 3_000, 1_000), Some(2));
+		assert_eq!(failed_instruction(&message, 4_000, 1_000), None);
+	}
+}
@@ -0,0 +1,96 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Where the extrinsics of the inspected block come from.
+
+use std::{error::Error, fs, path::Path};
+
+use codec::Decode;
+use subxt::{dynamic::Value, Config, OnlineClient, PolkadotConfig};
+
+use crate::dynamic::{as_bytes, as_u128, field, is_struct, items};
+
+/// The default node to fetch blocks from.
+pub const DEFAULT_URL: &str = "wss://rpc.polkadot.io:443";
+
+pub type Client = OnlineClient<PolkadotConfig>;
+pub type BlockHash = <PolkadotConfig as Config>::Hash;
+
+/// A block to inspect.
+pub struct Block {
+	/// The hash of the block, unknown for blocks read from a dump.
+	pub hash: Option<BlockHash>,
+	pub extrinsics: Vec<Vec<u8>>,
+}
+
+/// Fetches the block `block_number` from the node behind `api`.
+pub async fn fetch_block(api: &Client, block_number: u32) -> Result<Block, Box<dyn Error>> {
+	let block_hash = api
+		.rpc()
+		.block_hash(Some(block_number.into()))
+		.await?
+		.ok_or("block number not found")?;
+
+	let block = api.rpc().block(Some(block_hash)).await?.ok_or("block not found")?;
+	let extrinsics = block.block.extrinsics.into_iter().map(|extrinsic| extrinsic.0).collect();
+
+	Ok(Block { hash: Some(block_hash), extrinsics })
+}
+
+/// Fetches the downward messages queued for `para_id` at `block_hash`, with the relay block
+/// number they were sent at.
+///
+/// The queue is read dynamically, by name, so this works with any relay.
+pub async fn fetch_downward_messages(
+	api: &Client,
+	block_hash: BlockHash,
+	para_id: u32,
+) -> Result<Vec<(u32, Vec<u8>)>, Box<dyn Error>> {
+	let para_id = Value::unnamed_composite(vec![Value::u128(para_id.into())]);
+	let address = subxt::dynamic::storage("Dmp", "DownwardMessageQueues", vec![para_id]);
+	let queue = match api.storage().fetch(&address, Some(block_hash)).await? {
+		Some(queue) => queue.to_value()?,
+		None => return Ok(Vec::new()),
+	};
+
+	Ok(items(&queue, is_struct)
+		.into_iter()
+		.filter_map(|message| {
+			let sent_at = field(message, "sent_at").and_then(as_u128)? as u32;
+			Some((sent_at, field(message, "msg").and_then(as_bytes)?))
+		})
+		.collect())
+}
+
+/// Reads the extrinsics of a block dumped to `path`.
+///
+/// The dump is either JSON or the SCALE encoded block body (`Vec<Vec<u8>>`). JSON dumps may be
+/// the raw `chain_getBlock` response, its `result` or just the list of hex encoded extrinsics.
+pub fn read_block(path: &Path) -> Result<Block, Box<dyn Error>> {
+	let contents = fs::read(path)?;
+
+	let extrinsics = match contents.iter().find(|byte| !byte.is_ascii_whitespace()) {
+		Some(b'{') | Some(b'[') => extrinsics_from_json(&serde_json::from_slice(&contents)?)?,
+		_ => Decode::decode(&mut &contents[..])?,
+	};
+
+	Ok(Block { hash: None, extrinsics })
+}
+
+fn extrinsics_from_json(value: &serde_json::Value) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+	let extrinsics = ["/result/block/extrinsics", "/block/extrinsics", "/extrinsics", ""]
+		.iter()
+		.find_map(|pointer| value.pointer(pointer).and_then(|v| v.as_array()))
+		.ok_or("no extrinsics list found in the JSON dump")?;
+
+	extrinsics
+		.iter()
+		.map(|extrinsic| -> Result<Vec<u8>, Box<dyn Error>> {
+			let encoded = extrinsic.as_str().ok_or("extrinsics must be hex strings")?;
+			let bytes = hex::decode(encoded.trim_start_matches("0x"))?;
+			// Extrinsics are dumped with their length prefix, as `chain_getBlock` returns them.
+			Ok(Decode::decode(&mut &bytes[..])?)
+		})
+		.collect()
+}
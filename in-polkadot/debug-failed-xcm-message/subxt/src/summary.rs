@@ -0,0 +1,88 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Counts the messages found while scanning blocks, per para.
+
+use std::{collections::BTreeMap, fmt};
+
+use crate::processing::Report;
+
+/// The message counts of a para.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ParaCounts {
+	pub messages: u32,
+	/// Messages that could not be decoded as a `VersionedXcm`.
+	pub undecodable: u32,
+	/// Messages the relay failed to process, see [`Report::is_failure`].
+	pub failed: u32,
+}
+
+/// The message counts of every para messages were found for.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+	paras: BTreeMap<u32, ParaCounts>,
+}
+
+impl Summary {
+	/// Counts a message of `para_id`, with the relay-side processing `reports` found for it.
+	pub fn record(&mut self, para_id: u32, decoded: bool, reports: &[Report]) {
+		let counts = self.paras.entry(para_id).or_default();
+		counts.messages += 1;
+		if !decoded {
+			counts.undecodable += 1;
+		}
+		if reports.iter().any(Report::is_failure) {
+			counts.failed += 1;
+		}
+	}
+}
+
+impl fmt::Display for Summary {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "{:>8} {:>10} {:>12} {:>8}", "para", "messages", "undecodable", "failed")?;
+		for (para_id, counts) in &self.paras {
+			writeln!(
+				f,
+				"{:>8} {:>10} {:>12} {:>8}",
+				para_id, counts.messages, counts.undecodable, counts.failed
+			)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		polkadot::runtime_types::xcm::v2::traits::{Error, Outcome},
+		processing::Processing,
+	};
+
+	fn report(processing: Processing) -> Report {
+		Report { block_number: 1, processing }
+	}
+
+	#[test]
+	fn counts_messages_and_failures_per_para() {
+		let mut summary = Summary::default();
+		summary.record(2000, true, &[report(Processing::Executed(Outcome::Complete(1)))]);
+		summary.record(
+			2000,
+			true,
+			&[report(Processing::Executed(Outcome::Incomplete(1, Error::Barrier)))],
+		);
+		summary.record(1000, false, &[]);
+		summary.record(
+			1000,
+			true,
+			&[report(Processing::WeightExhausted { remaining: 1, required: 2 })],
+		);
+
+		assert_eq!(summary.paras[&1000], ParaCounts { messages: 2, undecodable: 1, failed: 0 });
+		assert_eq!(summary.paras[&2000], ParaCounts { messages: 2, undecodable: 0, failed: 1 });
+		let table = summary.to_string();
+		assert_eq!(table.lines().nth(1).unwrap().trim(), "1000          2            1        0");
+	}
+}
@@ -0,0 +1,210 @@
+// Copyright 2019-2022 Parity Technologies (UK) Ltd.
+// This file is dual-licensed as Apache-2.0 or GPL-3.0.
+// see LICENSE for license details.
+
+//! Decodes encoded XCM messages, locations and assets.
+//!
+//! The decoding functions are generic so they work with both the `staging-xcm` types and the
+//! types subxt generates from a chain's metadata, which encode the same way. [`transcode`] converts
+//! between the two.
+
+use std::fmt;
+
+use codec::{Decode, DecodeLimit, Encode};
+use scale_info::{meta_type, PortableRegistry, Registry, TypeInfo};
+use scale_value::{scale::TypeId, Value};
+use xcm::{VersionedMultiAssets, VersionedMultiLocation, VersionedXcm, MAX_XCM_DECODE_DEPTH};
+
+/// Why some data could not be decoded.
+#[derive(Debug)]
+pub enum Error {
+	/// The data is not valid hex.
+	Hex(hex::FromHexError),
+	/// The data does not encode the expected type.
+	Codec(codec::Error),
+	/// The data encodes the expected type, followed by this many unexpected bytes.
+	TrailingBytes(usize),
+	/// The data does not encode the expected type, as reported by `scale-value`.
+	Value(String),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Error::Hex(error) => write!(f, "invalid hex: {}", error),
+			Error::Codec(error) => write!(f, "unable to decode: {}", error),
+			Error::TrailingBytes(count) => write!(f, "{} bytes left after decoding", count),
+			Error::Value(error) => write!(f, "unable to decode: {}", error),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+impl From<hex::FromHexError> for Error {
+	fn from(error: hex::FromHexError) -> Self {
+		Error::Hex(error)
+	}
+}
+
+impl From<codec::Error> for Error {
+	fn from(error: codec::Error) -> Self {
+		Error::Codec(error)
+	}
+}
+
+/// Decodes a `T` from the whole of `data`.
+pub fn decode<T: Decode>(data: &[u8]) -> Result<T, Error> {
+	let mut remaining = data;
+	let value = T::decode_with_depth_limit(MAX_XCM_DECODE_DEPTH, &mut remaining)?;
+	match remaining.len() {
+		0 => Ok(value),
+		count => Err(Error::TrailingBytes(count)),
+	}
+}
+
+/// Decodes hex, with or without a `0x` prefix.
+pub fn from_hex(data: &str) -> Result<Vec<u8>, Error> {
+	let data = data.trim();
+	Ok(hex::decode(data.strip_prefix("0x").unwrap_or(data))?)
+}
+
+/// Decodes a `T` from the whole of the hex encoded `data`.
+pub fn decode_hex<T: Decode>(data: &str) -> Result<T, Error> {
+	decode(&from_hex(data)?)
+}
+
+/// Converts `value` to a type that encodes the same way, e.g. a subxt generated type to its
+/// `staging-xcm` counterpart.
+pub fn transcode<T: Encode, U: Decode>(value: &T) -> Result<U, Error> {
+	decode(&value.encode())
+}
+
+/// Splits `data`, made of concatenated encoded `T`s as in an XCMP message, into the encoding
+/// of each of them.
+pub fn split_concatenated<T: Decode>(mut data: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+	let mut encodings = Vec::new();
+	while !data.is_empty() {
+		let before = data;
+		T::decode_with_depth_limit(MAX_XCM_DECODE_DEPTH, &mut data)?;
+		encodings.push(before[..before.len() - data.len()].to_vec());
+	}
+	Ok(encodings)
+}
+
+/// Decodes a `T` from the whole of `data` into a [`Value`], which can be inspected by field and
+/// variant names without knowing `T`.
+pub fn decode_value<T: TypeInfo + 'static>(data: &[u8]) -> Result<Value<TypeId>, Error> {
+	let mut registry = Registry::new();
+	let type_id = registry.register_type(&meta_type::<T>()).id;
+	let registry = PortableRegistry::from(registry);
+
+	let mut remaining = data;
+	let value = scale_value::scale::decode_as_type(&mut remaining, type_id, &registry)
+		.map_err(|error| Error::Value(format!("{:?}", error)))?;
+	match remaining.len() {
+		0 => Ok(value),
+		count => Err(Error::TrailingBytes(count)),
+	}
+}
+
+/// Decodes an XCM message of any version. `Transact` calls are left encoded.
+pub fn versioned_xcm(data: &[u8]) -> Result<VersionedXcm<()>, Error> {
+	decode(data)
+}
+
+/// Decodes an XCM message of any version into a [`Value`], see [`decode_value`].
+pub fn versioned_xcm_value(data: &[u8]) -> Result<Value<TypeId>, Error> {
+	decode_value::<VersionedXcm<()>>(data)
+}
+
+/// Decodes a location of any version.
+pub fn versioned_multi_location(data: &[u8]) -> Result<VersionedMultiLocation, Error> {
+	decode(data)
+}
+
+/// Decodes assets of any version.
+pub fn versioned_multi_assets(data: &[u8]) -> Result<VersionedMultiAssets, Error> {
+	decode(data)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use xcm::latest::prelude::*;
+
+	fn message() -> VersionedXcm<()> {
+		VersionedXcm::from(Xcm(vec![
+			WithdrawAsset((Parent, 100u128).into()),
+			ClearOrigin,
+			DepositAsset { assets: AllCounted(1).into(), beneficiary: Parachain(1000).into() },
+		]))
+	}
+
+	#[test]
+	fn decodes_bytes_and_hex() {
+		let encoded = message().encode();
+
+		assert_eq!(versioned_xcm(&encoded).unwrap(), message());
+		assert_eq!(decode_hex::<VersionedXcm<()>>(&hex::encode(&encoded)).unwrap(), message());
+		assert_eq!(
+			decode_hex::<VersionedXcm<()>>(&format!(" 0x{}\n", hex::encode(&encoded))).unwrap(),
+			message()
+		);
+		assert!(matches!(decode_hex::<VersionedXcm<()>>("0xzz"), Err(Error::Hex(_))));
+	}
+
+	#[test]
+	fn decodes_older_versions() {
+		let location = VersionedMultiLocation::V2(xcm::v2::MultiLocation::parent());
+		assert_eq!(versioned_multi_location(&location.encode()).unwrap(), location);
+
+		let assets: MultiAssets = (Parent, 100u128).into();
+		let assets = VersionedMultiAssets::V2(assets.try_into().unwrap());
+		assert_eq!(versioned_multi_assets(&assets.encode()).unwrap(), assets);
+	}
+
+	#[test]
+	fn rejects_trailing_bytes() {
+		let mut encoded = message().encode();
+		encoded.extend([1, 2]);
+		assert!(matches!(versioned_xcm(&encoded), Err(Error::TrailingBytes(2))));
+	}
+
+	#[test]
+	fn transcodes_between_equivalent_types() {
+		#[derive(Encode, Decode, Debug, PartialEq)]
+		struct Location {
+			parents: u8,
+			interior: Junctions,
+		}
+
+		let location: Location = transcode(&MultiLocation::new(1, X1(Parachain(1000)))).unwrap();
+		assert_eq!(location, Location { parents: 1, interior: X1(Parachain(1000)) });
+	}
+
+	#[test]
+	fn decodes_into_values() {
+		let value = decode_value::<VersionedXcm<()>>(&message().encode()).unwrap();
+		match value.value {
+			scale_value::ValueDef::Variant(variant) => assert_eq!(variant.name, "V3"),
+			value => panic!("expected a variant, got {:?}", value),
+		}
+
+		let mut encoded = message().encode();
+		encoded.push(0);
+		assert!(matches!(decode_value::<VersionedXcm<()>>(&encoded), Err(Error::TrailingBytes(1))));
+	}
+
+	#[test]
+	fn splits_concatenated_messages() {
+		let first = message().encode();
+		let second = VersionedXcm::<()>::from(Xcm(vec![ClearError])).encode();
+
+		assert_eq!(
+			split_concatenated::<VersionedXcm<()>>(&[first.clone(), second.clone()].concat())
+				.unwrap(),
+			vec![first, second]
+		);
+	}
+}
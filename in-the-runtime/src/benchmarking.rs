@@ -4,27 +4,78 @@
 use super::*;
 
 use frame_benchmarking::v2::*;
+use frame_support::{traits::Get, BoundedVec};
 use frame_system::RawOrigin;
 
+/// Upper bound for the number of assets in a benchmarked call, the `MultiAssets` v3 limit.
+const MAX_ASSETS: u32 = 20;
+
 #[benchmarks]
 mod benches {
 	use super::*;
 
-	/// Benchmarks the slowest path of `change_value`.
+	/// Benchmarks trapping `n` distinct assets withdrawn from the caller.
+	#[benchmark]
+	fn trap_assets(n: Linear<1, MAX_ASSETS>) -> Result<(), BenchmarkError> {
+		let caller: T::AccountId = whitelisted_caller();
+		let assets = T::BenchmarkHelper::create_assets(&caller, n);
+
+		#[extrinsic_call]
+		_(RawOrigin::Signed(caller), assets);
+
+		Ok(())
+	}
+
+	/// Benchmarks claiming back `n` distinct assets previously trapped by the caller.
+	#[benchmark]
+	fn claim_assets(n: Linear<1, MAX_ASSETS>) -> Result<(), BenchmarkError> {
+		let caller: T::AccountId = whitelisted_caller();
+		let assets = T::BenchmarkHelper::create_assets(&caller, n);
+		Pallet::<T>::trap_assets(RawOrigin::Signed(caller.clone()).into(), assets.clone())?;
+
+		#[extrinsic_call]
+		_(RawOrigin::Signed(caller), assets, Here.into());
+
+		Ok(())
+	}
+
+	/// Benchmarks claiming `n` separately trapped sets of `a` assets in one batch.
 	#[benchmark]
-	fn change_value() -> Result<(), BenchmarkError> {
+	fn claim_many(
+		n: Linear<1, { T::MaxClaims::get() }>,
+		a: Linear<1, MAX_ASSETS>,
+	) -> Result<(), BenchmarkError> {
 		let caller: T::AccountId = whitelisted_caller();
+		let mut claims = Vec::new();
+		for _ in 0..n {
+			let assets = T::BenchmarkHelper::create_assets(&caller, a);
+			Pallet::<T>::trap_assets(RawOrigin::Signed(caller.clone()).into(), assets.clone())?;
+			claims.push((assets, Location::from(Here)));
+		}
+		let claims =
+			BoundedVec::try_from(claims).map_err(|_| BenchmarkError::Stop("too many claims"))?;
+
+		#[extrinsic_call]
+		_(RawOrigin::Signed(caller), claims);
+
+		Ok(())
+	}
 
-		// You can mock the storage here:
-		DummyValue::<T>::put(1);
+	/// Benchmarks sending a claim of `n` assets to another chain.
+	#[benchmark]
+	fn claim_remote(n: Linear<1, MAX_ASSETS>) -> Result<(), BenchmarkError> {
+		let caller: T::AccountId = whitelisted_caller();
+		let dest = T::BenchmarkHelper::reachable_dest();
+		let assets = T::BenchmarkHelper::create_assets(&caller, n);
+		let beneficiary = Location::from(Here);
 
 		#[extrinsic_call]
-		_(RawOrigin::Signed(caller.clone()), 9);
+		_(RawOrigin::Signed(caller), dest, assets, Here.into(), 0, beneficiary);
 
 		Ok(())
 	}
 
 	// Implements a test for each benchmark. Execute with:
-	// `cargo test -p pallet-activityRuntimeXcm --features runtime-benchmarks`.
+	// `cargo test -p pallet-activity-runtime-xcm --features runtime-benchmarks`.
 	impl_benchmark_test_suite!(Pallet, crate::mock::new_test_ext(), crate::mock::Test);
 }
@@ -34,11 +34,23 @@ mod benchmarking;
 mod mock;
 mod tests;
 
+pub mod migration;
 pub mod weights;
 
-use sp_runtime::traits::TryConvert;
-
-use xcm::prelude::*;
+use codec::{Decode, Encode};
+use frame_support::storage::{with_transaction, TransactionOutcome};
+use scale_info::TypeInfo;
+use sp_core::H256;
+use sp_runtime::{
+	traits::{BlakeTwo256, Hash, Saturating, TryConvert},
+	DispatchError, RuntimeDebug,
+};
+
+use xcm::{prelude::*, VersionedMultiAssets, VersionedMultiLocation};
+use xcm_executor::{
+	traits::{ClaimAssets, DropAssets},
+	Assets as HoldingAssets,
+};
 
 /// Whatever type is needed for claiming assets
 // TODO: Modify as needed
@@ -47,30 +59,66 @@ pub struct Ticket;
 type Location = MultiLocation;
 type Assets = MultiAssets;
 
-pub trait WeightInfo {
-	fn trap_assets() -> Weight;
-	fn claim_assets() -> Weight;
+/// An entry of the trapped assets index.
+#[derive(Encode, Decode, Clone, PartialEq, Eq, RuntimeDebug, TypeInfo)]
+pub struct TrapRecord {
+	/// How many times the same assets were trapped for the same origin.
+	pub count: u32,
+	/// The origin and assets behind the trap, unknown for traps indexed by a migration.
+	pub details: Option<(VersionedMultiLocation, VersionedMultiAssets)>,
 }
 
-impl WeightInfo for () {
-	fn trap_assets() -> Weight {
-		Weight::zero()
-	}
+/// Computes the key under which `pallet_xcm` stores assets trapped for `origin`.
+pub fn trap_hash(origin: &Location, assets: &Assets) -> H256 {
+	BlakeTwo256::hash_of(&(origin, VersionedMultiAssets::from(assets.clone())))
+}
 
-	fn claim_assets() -> Weight {
-		Weight::zero()
-	}
+/// Computes the key of the trap that `pallet_xcm` claims `assets` from with `ticket`, which may
+/// ask for the assets to be hashed at an older XCM version.
+pub fn claim_hash(origin: &Location, ticket: &Location, assets: &Assets) -> Option<H256> {
+	let versioned = VersionedMultiAssets::from(assets.clone());
+	let versioned = match (ticket.parents, &ticket.interior) {
+		(0, X1(GeneralIndex(version))) => versioned.into_version(*version as u32).ok()?,
+		(0, Here) => versioned,
+		_ => return None,
+	};
+	Some(BlakeTwo256::hash_of(&(origin, versioned)))
 }
 
+/// The largest number of assets in any of `claims`, which `claim_many` is weighed by.
+pub fn largest_claim(claims: &[(Assets, Location)]) -> u32 {
+	claims.iter().map(|(assets, _)| assets.len() as u32).max().unwrap_or(0)
+}
+
+pub use weights::WeightInfo;
+
 pub struct TestWeightInfo;
 impl WeightInfo for TestWeightInfo {
-	fn trap_assets() -> Weight {
+	fn trap_assets(_n: u32) -> Weight {
 		Weight::from_parts(1_000_000, 1_000_000)
 	}
 
-	fn claim_assets() -> Weight {
+	fn claim_assets(_n: u32) -> Weight {
 		Weight::from_parts(1_000_000, 1_000_000)
 	}
+
+	fn claim_many(n: u32, _a: u32) -> Weight {
+		Weight::from_parts(1_000_000, 1_000_000).saturating_mul(n.into())
+	}
+
+	fn claim_remote(_n: u32) -> Weight {
+		Weight::from_parts(1_000_000, 1_000_000)
+	}
+}
+
+/// Provides the assets used when benchmarking the pallet's dispatchables.
+#[cfg(feature = "runtime-benchmarks")]
+pub trait BenchmarkHelper<AccountId> {
+	/// Returns `n` distinct assets that `who` is able to withdraw.
+	fn create_assets(who: &AccountId, n: u32) -> Assets;
+
+	/// Returns a destination the configured `XcmRouter` is able to send to.
+	fn reachable_dest() -> Location;
 }
 
 #[frame_support::pallet]
@@ -79,9 +127,19 @@ pub mod pallet {
 	use frame_support::pallet_prelude::*;
 	use frame_system::pallet_prelude::*;
 
+	/// The in-code storage version.
+	const STORAGE_VERSION: StorageVersion = StorageVersion::new(1);
+
 	#[pallet::pallet]
+	#[pallet::storage_version(STORAGE_VERSION)]
+	#[pallet::without_storage_info]
 	pub struct Pallet<T>(_);
 
+	/// Index of the assets trapped by `pallet_xcm`, keyed like its `AssetTraps`.
+	#[pallet::storage]
+	#[pallet::getter(fn trap_index)]
+	pub type TrapIndex<T: Config> = StorageMap<_, Identity, H256, TrapRecord>;
+
 	#[pallet::config]
 	pub trait Config: frame_system::Config {
 		/// The overarching event type of the runtime.
@@ -92,60 +150,180 @@ pub mod pallet {
 
 		type LocationConverter: TryConvert<<Self as frame_system::Config>::RuntimeOrigin, Location>;
 
+		/// The asset trap the pallet wraps, configure the pallet as the executor's `AssetTrap`
+		/// so that the index follows every trap.
+		type AssetTrap: DropAssets;
+
+		/// The trapped assets claimer the pallet wraps, configure the pallet as the executor's
+		/// `AssetClaims` so that the index follows every claim. Also used to check claims upfront.
+		type AssetClaims: ClaimAssets;
+
+		/// The router used to send claims to other chains.
+		type XcmRouter: SendXcm;
+
+		/// The maximum number of claims that can be batched in a single call.
+		#[pallet::constant]
+		type MaxClaims: Get<u32>;
+
 		/// Weight information for all calls of this pallet.
 		type WeightInfo: WeightInfo;
+
+		/// Helper to create the assets used by the benchmarks.
+		#[cfg(feature = "runtime-benchmarks")]
+		type BenchmarkHelper: BenchmarkHelper<Self::AccountId>;
 	}
 
 	#[pallet::event]
-	pub enum Event<T: Config> {}
+	#[pallet::generate_deposit(pub(super) fn deposit_event)]
+	pub enum Event<T: Config> {
+		/// A claim in a batch was processed.
+		ClaimProcessed { index: u32, success: bool },
+		/// A claim of trapped assets was sent to another chain.
+		RemoteClaimSent { dest: Location, message_id: XcmHash },
+	}
 
 	// Errors inform users that something went wrong.
 	#[pallet::error]
 	pub enum Error<T> {
 		/// The origin could not be converted to an XCM location
 		BadOrigin,
+		/// None of the batched claims matches assets trapped for the origin
+		NothingToClaim,
+		/// The fee asset index does not point to any of the claimed assets
+		BadFeeAsset,
+		/// The claim could not be sent to the destination
+		SendFailure,
+		/// The XCM did not execute completely
+		ExecutionFailed,
+		/// The origin could not pay the delivery fees of the claim
+		FeesNotMet,
 	}
 
-	#[pallet::call(weight(<T as Config>::WeightInfo))]
+	#[pallet::call]
 	impl<T: Config> Pallet<T> {
-		/* ------------------------------------------------------------------------- */
-		// Proposed activity:
-		// Modify the following extrinsics to
-		// 1.) Create a dispatchable that creates an XCM. Such XCM results in some funds being
-		// trapped after the message exeecution.
-		//
-		// 2.) Create a dispatchable that craetes an XCM which claims some trapped funds as a
-		// result of the execution of the message.
-		//
-		// Usually trapping funds is not a desired outcome.
-		// In the first XCM, what would you do to avoid trapping the assets?
-
-		/// Composes and executes an XCM that traps some funds
+		/// Composes and executes an XCM that traps some funds.
+		///
+		/// The assets are withdrawn from the origin into the holding register and left there, so
+		/// the executor traps them once the message finishes.
 		#[pallet::call_index(0)]
-		pub fn trap_assets(origin: OriginFor<T>, _assets: Assets) -> DispatchResult {
-			// TODO: Build the XCM
+		#[pallet::weight(T::WeightInfo::trap_assets(assets.len() as u32))]
+		pub fn trap_assets(origin: OriginFor<T>, assets: Assets) -> DispatchResult {
 			let message = Xcm(vec![
-				// Instructions
+				UnpaidExecution { weight_limit: Unlimited, check_origin: None },
+				WithdrawAsset(assets),
 			]);
 
-			Self::do_execute_xcm(origin, message)?;
+			let outcome = Self::do_execute_xcm(origin, message)?;
+			outcome.ensure_complete().map_err(|_| Error::<T>::ExecutionFailed)?;
 
 			Ok(())
 		}
 
-		/// Composes and executes and XCM that claims some trapped funds
+		/// Composes and executes and XCM that claims some trapped funds.
+		///
+		/// The claimed assets are deposited back into the account of the origin.
 		#[pallet::call_index(1)]
+		#[pallet::weight(T::WeightInfo::claim_assets(assets.len() as u32))]
 		pub fn claim_assets(
 			origin: OriginFor<T>,
-			_assets: Assets,
-			_ticket: Location,
+			assets: Assets,
+			ticket: Location,
 		) -> DispatchResult {
-			// TODO: Build the XCM
+			let beneficiary = T::LocationConverter::try_convert(origin.clone())
+				.map_err(|_| Error::<T>::BadOrigin)?;
+			let count = assets.len() as u32;
+
 			let message = Xcm(vec![
-				// Instructions
+				UnpaidExecution { weight_limit: Unlimited, check_origin: None },
+				ClaimAsset { assets, ticket },
+				DepositAsset { assets: Wild(AllCounted(count)), beneficiary },
+			]);
+
+			let outcome = Self::do_execute_xcm(origin, message)?;
+			outcome.ensure_complete().map_err(|_| Error::<T>::ExecutionFailed)?;
+
+			Ok(())
+		}
+
+		/// Composes and executes a single XCM that claims several sets of trapped funds.
+		///
+		/// Every claim is checked before building the message and only the claimable ones are
+		/// included, so one stale claim does not prevent the others. Claims are checked in order
+		/// against the traps left by the ones before them, so repeating a claim only succeeds as
+		/// many times as the assets were trapped. The outcome of each claim is reported through a
+		/// `ClaimProcessed` event, in the order they were given.
+		#[pallet::call_index(2)]
+		#[pallet::weight(T::WeightInfo::claim_many(claims.len() as u32, largest_claim(claims)))]
+		pub fn claim_many(
+			origin: OriginFor<T>,
+			claims: BoundedVec<(Assets, Location), T::MaxClaims>,
+		) -> DispatchResult {
+			let beneficiary = T::LocationConverter::try_convert(origin.clone())
+				.map_err(|_| Error::<T>::BadOrigin)?;
+
+			let claimable = Self::claimable(&beneficiary, &claims);
+			ensure!(claimable.contains(&true), Error::<T>::NothingToClaim);
+
+			let mut count = 0u32;
+			let mut instructions =
+				vec![UnpaidExecution { weight_limit: Unlimited, check_origin: None }];
+			for ((assets, ticket), claimable) in claims.iter().zip(claimable.iter()) {
+				if *claimable {
+					count = count.saturating_add(assets.len() as u32);
+					instructions.push(ClaimAsset { assets: assets.clone(), ticket: *ticket });
+				}
+			}
+			instructions.push(DepositAsset { assets: Wild(AllCounted(count)), beneficiary });
+
+			let outcome = Self::do_execute_xcm(origin, Xcm(instructions))?;
+			let complete = outcome.ensure_complete().is_ok();
+
+			for (index, claimable) in claimable.into_iter().enumerate() {
+				let success = claimable && complete;
+				Self::deposit_event(Event::ClaimProcessed { index: index as u32, success });
+			}
+
+			Ok(())
+		}
+
+		/// Composes and sends an XCM that claims some funds trapped on another chain.
+		///
+		/// The message descends into the origin's location, so the destination looks up the traps
+		/// of the origin as it sees it. Execution is paid with the claimed asset at
+		/// `fee_asset_item` and whatever remains is deposited into `beneficiary`. Both `assets`
+		/// and `beneficiary` are interpreted from the destination's point of view. The origin pays
+		/// the price of delivering the message, like `pallet_xcm::send` does.
+		#[pallet::call_index(3)]
+		#[pallet::weight(T::WeightInfo::claim_remote(assets.len() as u32))]
+		pub fn claim_remote(
+			origin: OriginFor<T>,
+			dest: Location,
+			assets: Assets,
+			ticket: Location,
+			fee_asset_item: u32,
+			beneficiary: Location,
+		) -> DispatchResult {
+			let origin_location =
+				T::LocationConverter::try_convert(origin).map_err(|_| Error::<T>::BadOrigin)?;
+			ensure!(origin_location.parents == 0, Error::<T>::BadOrigin);
+			let fees = assets.get(fee_asset_item as usize).ok_or(Error::<T>::BadFeeAsset)?.clone();
+			let count = assets.len() as u32;
+
+			let mut message = Xcm(vec![
+				ClaimAsset { assets, ticket },
+				BuyExecution { fees, weight_limit: Unlimited },
+				DepositAsset { assets: Wild(AllCounted(count)), beneficiary },
 			]);
+			if origin_location.interior != Here {
+				message.0.insert(0, DescendOrigin(origin_location.interior));
+			}
 
-			Self::do_execute_xcm(origin, message)?;
+			let (ticket, price) = validate_send::<T::XcmRouter>(dest, message)
+				.map_err(|_| Error::<T>::SendFailure)?;
+			T::XcmExecutor::charge_fees(origin_location, price)
+				.map_err(|_| Error::<T>::FeesNotMet)?;
+			let message_id = T::XcmRouter::deliver(ticket).map_err(|_| Error::<T>::SendFailure)?;
+			Self::deposit_event(Event::RemoteClaimSent { dest, message_id });
 
 			Ok(())
 		}
@@ -157,12 +335,12 @@ pub mod pallet {
 		pub(crate) fn do_execute_xcm(
 			origin: OriginFor<T>,
 			message: Xcm<<T as frame_system::Config>::RuntimeCall>,
-		) -> Result<(), Error<T>> {
+		) -> Result<Outcome, Error<T>> {
 			let mut hash = message.using_encoded(sp_io::hashing::blake2_256);
 			let origin_location =
 				T::LocationConverter::try_convert(origin).map_err(|_| Error::BadOrigin)?;
 
-			T::XcmExecutor::prepare_and_execute(
+			let outcome = T::XcmExecutor::prepare_and_execute(
 				origin_location,
 				message,
 				&mut hash,
@@ -171,7 +349,74 @@ pub mod pallet {
 				Weight::zero(),
 			);
 
-			Ok(())
+			Ok(outcome)
+		}
+
+		/// Records in the index that `assets` were trapped for `origin`.
+		fn note_trapped(origin: &Location, assets: &Assets) {
+			TrapIndex::<T>::mutate(trap_hash(origin, assets), |record| {
+				let record = record.get_or_insert(TrapRecord { count: 0, details: None });
+				record.count.saturating_inc();
+				record.details = Some(((*origin).into(), assets.clone().into()));
+			});
+		}
+
+		/// Records in the index that `assets` trapped for `origin` were claimed with `ticket`.
+		fn note_claimed(origin: &Location, ticket: &Location, assets: &Assets) {
+			let Some(hash) = claim_hash(origin, ticket, assets) else { return };
+			TrapIndex::<T>::mutate_exists(hash, |maybe_record| {
+				if let Some(record) = maybe_record {
+					record.count.saturating_dec();
+					if record.count == 0 {
+						*maybe_record = None;
+					}
+				}
+			});
+		}
+
+		/// Checks which of `claims` `origin` could make one after the other, leaving the traps
+		/// untouched.
+		fn claimable(origin: &Location, claims: &[(Assets, Location)]) -> Vec<bool> {
+			let context = XcmContext { origin: Some(*origin), message_id: [0; 32], topic: None };
+			with_transaction(|| {
+				let claimable = claims
+					.iter()
+					.map(|(assets, ticket)| {
+						T::AssetClaims::claim_assets(origin, ticket, assets, &context)
+					})
+					.collect();
+				TransactionOutcome::Rollback(Ok::<_, DispatchError>(claimable))
+			})
+			.unwrap_or_else(|_| vec![false; claims.len()])
+		}
+	}
+
+	impl<T: Config> DropAssets for Pallet<T> {
+		fn drop_assets(origin: &Location, assets: HoldingAssets, context: &XcmContext) -> Weight {
+			let trapped = (!assets.is_empty()).then(|| Assets::from(assets.clone()));
+			let weight = T::AssetTrap::drop_assets(origin, assets, context);
+			match trapped {
+				Some(trapped) => {
+					Self::note_trapped(origin, &trapped);
+					weight.saturating_add(T::DbWeight::get().reads_writes(1, 1))
+				},
+				None => weight,
+			}
+		}
+	}
+
+	impl<T: Config> ClaimAssets for Pallet<T> {
+		fn claim_assets(
+			origin: &Location,
+			ticket: &Location,
+			what: &Assets,
+			context: &XcmContext,
+		) -> bool {
+			let claimed = T::AssetClaims::claim_assets(origin, ticket, what, context);
+			if claimed {
+				Self::note_claimed(origin, ticket, what);
+			}
+			claimed
 		}
 	}
 }
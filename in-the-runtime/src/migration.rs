@@ -0,0 +1,105 @@
+// Copyright Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Storage migrations for the activity pallet.
+
+use crate::{Config, Pallet, TrapIndex, TrapRecord};
+use frame_support::{
+	migration::storage_key_iter,
+	pallet_prelude::*,
+	traits::{OnRuntimeUpgrade, PalletInfoAccess},
+};
+use sp_core::H256;
+use sp_runtime::Saturating;
+
+#[cfg(feature = "try-runtime")]
+use sp_runtime::TryRuntimeError;
+
+/// Iterates over the `AssetTraps` storage of `pallet_xcm`, which the pallet does not expose.
+fn asset_traps<T: pallet_xcm::Config>() -> impl Iterator<Item = (H256, u32)> {
+	let pallet_name = <pallet_xcm::Pallet<T> as PalletInfoAccess>::name();
+	storage_key_iter::<H256, u32, Identity>(pallet_name.as_bytes(), b"AssetTraps")
+}
+
+/// Reads the number of times the assets behind `hash` are trapped in `pallet_xcm`.
+#[cfg(feature = "try-runtime")]
+fn asset_trap<T: pallet_xcm::Config>(hash: &H256) -> Option<u32> {
+	let pallet_name = <pallet_xcm::Pallet<T> as PalletInfoAccess>::name();
+	frame_support::migration::get_storage_value(
+		pallet_name.as_bytes(),
+		b"AssetTraps",
+		hash.as_ref(),
+	)
+}
+
+pub mod v1 {
+	use super::*;
+
+	/// Backfills the `TrapIndex` from the traps already held by `pallet_xcm`.
+	///
+	/// The origin and assets behind existing traps cannot be recovered from their hash, so the
+	/// backfilled entries only carry the trap count.
+	pub struct MigrateToV1<T>(PhantomData<T>);
+
+	impl<T: Config + pallet_xcm::Config> OnRuntimeUpgrade for MigrateToV1<T> {
+		fn on_runtime_upgrade() -> Weight {
+			let on_chain_version = Pallet::<T>::on_chain_storage_version();
+			if on_chain_version != 0 {
+				log::info!(
+					target: "runtime::activity",
+					"skipping v1 migration, on-chain storage version is {:?}",
+					on_chain_version,
+				);
+				return T::DbWeight::get().reads(1)
+			}
+
+			let mut reads = 1u64;
+			let mut writes = 0u64;
+			for (hash, count) in asset_traps::<T>() {
+				reads.saturating_accrue(2);
+				if !TrapIndex::<T>::contains_key(hash) {
+					TrapIndex::<T>::insert(hash, TrapRecord { count, details: None });
+					writes.saturating_inc();
+				}
+			}
+
+			StorageVersion::new(1).put::<Pallet<T>>();
+			writes.saturating_inc();
+
+			log::info!(target: "runtime::activity", "indexed {} asset traps", writes - 1);
+			T::DbWeight::get().reads_writes(reads, writes)
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade(_state: Vec<u8>) -> Result<(), TryRuntimeError> {
+			ensure!(
+				Pallet::<T>::on_chain_storage_version() == 1,
+				"storage version was not bumped to 1"
+			);
+			for (hash, count) in asset_traps::<T>() {
+				ensure!(
+					TrapIndex::<T>::get(hash).map(|record| record.count) == Some(count),
+					"an index entry does not match its asset trap"
+				);
+			}
+			for hash in TrapIndex::<T>::iter_keys() {
+				ensure!(asset_trap::<T>(&hash).is_some(), "an index entry has no asset trap");
+			}
+
+			Ok(())
+		}
+	}
+}
@@ -16,6 +16,7 @@
 
 #![cfg(test)]
 
+use codec::Encode;
 use frame_support::{
 	derive_impl, parameter_types,
 	traits::{ConstU32, ConstU64, Everything, Nothing},
@@ -25,8 +26,10 @@ use sp_runtime::{
 	traits::{IdentityLookup, TryConvert},
 	AccountId32, BuildStorage,
 };
+use std::cell::RefCell;
 use xcm::prelude::*;
 use xcm_builder::SignedToAccountId32;
+use xcm_executor::traits::TransactAsset;
 
 type Location = MultiLocation;
 
@@ -91,6 +94,7 @@ parameter_types! {
 	pub TrustedAssets: (MultiAssetFilter, MultiLocation) = (All.into(), Here.into());
 	pub const MaxInstructions: u32 = 100;
 	pub const MaxAssetsIntoHolding: u32 = 64;
+	pub const DeliveryFee: Balance = 5;
 }
 
 pub type LocalOriginToLocation = SignedToAccountId32<RuntimeOrigin, AccountId, AnyNetwork>;
@@ -118,8 +122,6 @@ impl pallet_xcm::Config for Test {
 	type MaxRemoteLockConsumers = ConstU32<0>;
 	type RemoteLockConsumerIdentifier = ();
 	type WeightInfo = pallet_xcm::TestWeightInfo;
-	#[cfg(feature = "runtime-benchmarks")]
-	type ReachableDest = ReachableDest;
 	type AdminOrigin = EnsureRoot<AccountId>;
 }
 
@@ -145,11 +147,77 @@ pub type LocalAssetTransactor = xcm_builder::CurrencyAdapter<
 	(),
 >;
 
+/// Accepts any fungible asset identified by a local `GeneralIndex` without keeping balances, so
+/// that the benchmarks can put many distinct assets into the holding register.
+pub struct IndexedAssetTransactor;
+impl IndexedAssetTransactor {
+	fn is_indexed(what: &MultiAsset) -> bool {
+		matches!(
+			what,
+			MultiAsset {
+				id: Concrete(MultiLocation { parents: 0, interior: X1(GeneralIndex(_)) }),
+				fun: Fungible(_),
+			}
+		)
+	}
+}
+
+impl TransactAsset for IndexedAssetTransactor {
+	fn deposit_asset(
+		what: &MultiAsset,
+		_who: &MultiLocation,
+		_context: Option<&XcmContext>,
+	) -> XcmResult {
+		Self::is_indexed(what).then_some(()).ok_or(XcmError::AssetNotFound)
+	}
+
+	fn withdraw_asset(
+		what: &MultiAsset,
+		_who: &MultiLocation,
+		_maybe_context: Option<&XcmContext>,
+	) -> Result<xcm_executor::Assets, XcmError> {
+		Self::is_indexed(what)
+			.then(|| what.clone().into())
+			.ok_or(XcmError::AssetNotFound)
+	}
+}
+
+pub type AssetTransactors = (LocalAssetTransactor, IndexedAssetTransactor);
+
+thread_local! {
+	pub static SENT_XCM: RefCell<Vec<(MultiLocation, Xcm<()>)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Returns the messages sent through `TestSendXcm`.
+pub fn sent_xcm() -> Vec<(MultiLocation, Xcm<()>)> {
+	SENT_XCM.with(|q| q.borrow().clone())
+}
+
+/// Router that accepts any destination for a `DeliveryFee` and records the messages sent to it.
+pub struct TestSendXcm;
+impl SendXcm for TestSendXcm {
+	type Ticket = (MultiLocation, Xcm<()>);
+
+	fn validate(
+		dest: &mut Option<MultiLocation>,
+		msg: &mut Option<Xcm<()>>,
+	) -> SendResult<Self::Ticket> {
+		let pair = (dest.take().ok_or(SendError::MissingArgument)?, msg.take().unwrap());
+		Ok((pair, (Here, DeliveryFee::get()).into()))
+	}
+
+	fn deliver(pair: Self::Ticket) -> Result<XcmHash, SendError> {
+		let hash = pair.1.using_encoded(sp_io::hashing::blake2_256);
+		SENT_XCM.with(|q| q.borrow_mut().push(pair));
+		Ok(hash)
+	}
+}
+
 pub struct XcmConfig;
 impl xcm_executor::Config for XcmConfig {
 	type RuntimeCall = RuntimeCall;
 	type XcmSender = (); // We only execute messages locally
-	type AssetTransactor = LocalAssetTransactor;
+	type AssetTransactor = AssetTransactors;
 	type OriginConverter = ();
 	type IsReserve = ();
 	type IsTeleporter = ();
@@ -158,10 +226,10 @@ impl xcm_executor::Config for XcmConfig {
 	type Weigher = xcm_builder::FixedWeightBounds<UnitWeightCost, RuntimeCall, MaxInstructions>;
 	type Trader = xcm_builder::FixedRateOfFungible<CurrencyPerSecondPerByte, ()>;
 	type ResponseHandler = PalletXcm;
-	type AssetTrap = PalletXcm;
+	type AssetTrap = ActivityPallet;
 	type AssetLocker = ();
 	type AssetExchanger = ();
-	type AssetClaims = PalletXcm;
+	type AssetClaims = ActivityPallet;
 	type SubscriptionService = ();
 	type PalletInstancesInfo = ();
 	type FeeManager = ();
@@ -178,10 +246,35 @@ impl crate::Config for Test {
 	type WeightInfo = ();
 	type XcmExecutor = xcm_executor::XcmExecutor<XcmConfig>;
 	type LocationConverter = SignedToAccountId32<RuntimeOrigin, AccountId, ThisNetwork>;
+	type AssetTrap = PalletXcm;
+	type AssetClaims = PalletXcm;
+	type XcmRouter = TestSendXcm;
+	type MaxClaims = ConstU32<8>;
+	#[cfg(feature = "runtime-benchmarks")]
+	type BenchmarkHelper = MockBenchmarkHelper;
 }
 
 pub const INITIAL_BALANCE: Balance = 100;
 
+/// Funds the account with the native token and pads the assets with indexed ones.
+#[cfg(feature = "runtime-benchmarks")]
+pub struct MockBenchmarkHelper;
+#[cfg(feature = "runtime-benchmarks")]
+impl crate::BenchmarkHelper<AccountId> for MockBenchmarkHelper {
+	fn create_assets(who: &AccountId, n: u32) -> MultiAssets {
+		use frame_support::traits::Currency;
+
+		Balances::make_free_balance_be(who, INITIAL_BALANCE);
+		let mut assets: Vec<MultiAsset> = vec![(Here, INITIAL_BALANCE / 2).into()];
+		assets.extend((1..n).map(|i| (X1(GeneralIndex(i as u128)), 1u128).into()));
+		assets.into()
+	}
+
+	fn reachable_dest() -> MultiLocation {
+		Parent.into()
+	}
+}
+
 // Build genesis storage according to the mock runtime.
 pub fn new_test_ext() -> sp_io::TestExternalities {
 	let mut t = frame_system::GenesisConfig::<Test>::default().build_storage().unwrap();
@@ -204,6 +297,18 @@ pub fn assets_have_been_trapped() -> bool {
 		.is_some()
 }
 
+pub fn claims_processed() -> Vec<(u32, bool)> {
+	System::events()
+		.into_iter()
+		.map(|r| r.event)
+		.filter_map(|event| match event {
+			RuntimeEvent::ActivityPallet(crate::Event::ClaimProcessed { index, success }) =>
+				Some((index, success)),
+			_ => None,
+		})
+		.collect()
+}
+
 pub fn assets_have_been_claimed() -> bool {
 	System::events()
 		.into_iter()
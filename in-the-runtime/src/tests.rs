@@ -16,11 +16,17 @@
 
 #![cfg(test)]
 
-use crate::mock::*;
-use frame_support::assert_ok;
+use crate::{migration::v1::MigrateToV1, mock::*, TrapRecord};
+use frame_support::{
+	assert_noop, assert_ok,
+	migration::put_storage_value,
+	traits::{GetStorageVersion, OnRuntimeUpgrade, StorageVersion},
+	weights::Weight,
+};
 use sp_runtime::AccountId32;
 use test_log::test;
 use xcm::prelude::*;
+use xcm_executor::{traits::DropAssets, XcmExecutor};
 
 type Location = MultiLocation;
 type Assets = MultiAssets;
@@ -61,3 +67,304 @@ fn claim_assets_works() {
 		assert_eq!(Balances::free_balance(&account), INITIAL_BALANCE);
 	});
 }
+
+#[test]
+fn trap_assets_fails_without_funds() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(System::block_number() + 1);
+		let account: AccountId32 = [0; 32].into();
+		let assets: Assets = vec![(Here, INITIAL_BALANCE + 1).into()].into();
+		assert_noop!(
+			ActivityPallet::trap_assets(RuntimeOrigin::signed(account), assets),
+			crate::Error::<Test>::ExecutionFailed
+		);
+	});
+}
+
+#[test]
+fn claim_assets_fails_without_trapped_assets() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(System::block_number() + 1);
+		let account: AccountId32 = [0; 32].into();
+		let assets: Assets = vec![(Here, 10).into()].into();
+		assert_noop!(
+			ActivityPallet::claim_assets(RuntimeOrigin::signed(account), assets, Here.into()),
+			crate::Error::<Test>::ExecutionFailed
+		);
+	});
+}
+
+#[test]
+fn claim_many_works() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(System::block_number() + 1);
+		let account: AccountId32 = [0; 32].into();
+		let first: Assets = vec![(Here, 10).into()].into();
+		let second: Assets = vec![(Here, 20).into()].into();
+		for assets in [first.clone(), second.clone()] {
+			assert_ok!(ActivityPallet::trap_assets(RuntimeOrigin::signed(account.clone()), assets));
+		}
+		assert_eq!(Balances::free_balance(&account), INITIAL_BALANCE - 30);
+
+		let ticket: Location = Here.into();
+		let claims = vec![(first, ticket), (second, ticket)].try_into().unwrap();
+		assert_ok!(ActivityPallet::claim_many(RuntimeOrigin::signed(account.clone()), claims));
+
+		assert_eq!(claims_processed(), vec![(0, true), (1, true)]);
+		assert_eq!(Balances::free_balance(&account), INITIAL_BALANCE);
+	});
+}
+
+#[test]
+fn claim_many_skips_unknown_claims() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(System::block_number() + 1);
+		let account: AccountId32 = [0; 32].into();
+		let trapped: Assets = vec![(Here, 10).into()].into();
+		let never_trapped: Assets = vec![(Here, 15).into()].into();
+		assert_ok!(ActivityPallet::trap_assets(
+			RuntimeOrigin::signed(account.clone()),
+			trapped.clone()
+		));
+
+		let ticket: Location = Here.into();
+		let claims = vec![(never_trapped, ticket), (trapped, ticket)].try_into().unwrap();
+		assert_ok!(ActivityPallet::claim_many(RuntimeOrigin::signed(account.clone()), claims));
+
+		assert_eq!(claims_processed(), vec![(0, false), (1, true)]);
+		assert_eq!(Balances::free_balance(&account), INITIAL_BALANCE);
+	});
+}
+
+#[test]
+fn claim_many_only_repeats_claims_as_often_as_trapped() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(System::block_number() + 1);
+		let account: AccountId32 = [0; 32].into();
+		let assets: Assets = vec![(Here, 10).into()].into();
+		assert_ok!(ActivityPallet::trap_assets(
+			RuntimeOrigin::signed(account.clone()),
+			assets.clone()
+		));
+
+		let ticket: Location = Here.into();
+		let claims = vec![(assets.clone(), ticket), (assets, ticket)].try_into().unwrap();
+		assert_ok!(ActivityPallet::claim_many(RuntimeOrigin::signed(account.clone()), claims));
+
+		assert_eq!(claims_processed(), vec![(0, true), (1, false)]);
+		assert_eq!(Balances::free_balance(&account), INITIAL_BALANCE);
+	});
+}
+
+#[test]
+fn claim_many_fails_without_claimable_assets() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(System::block_number() + 1);
+		let account: AccountId32 = [0; 32].into();
+		let assets: Assets = vec![(Here, 10).into()].into();
+
+		let ticket: Location = Here.into();
+		let claims = vec![(assets, ticket)].try_into().unwrap();
+		assert_noop!(
+			ActivityPallet::claim_many(RuntimeOrigin::signed(account), claims),
+			crate::Error::<Test>::NothingToClaim
+		);
+	});
+}
+
+#[test]
+fn claim_remote_sends_claim_program() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(System::block_number() + 1);
+		let account: AccountId32 = [0; 32].into();
+		let dest: Location = Parent.into();
+		let assets: Assets = vec![(Parent, 10).into()].into();
+		let ticket: Location = Here.into();
+		let beneficiary: Location =
+			Junction::AccountId32 { network: None, id: account.clone().into() }.into();
+
+		assert_ok!(ActivityPallet::claim_remote(
+			RuntimeOrigin::signed(account.clone()),
+			dest,
+			assets.clone(),
+			ticket,
+			0,
+			beneficiary,
+		));
+
+		let expected = Xcm(vec![
+			DescendOrigin(X1(Junction::AccountId32 { network: None, id: account.clone().into() })),
+			ClaimAsset { assets, ticket },
+			BuyExecution { fees: (Parent, 10).into(), weight_limit: Unlimited },
+			DepositAsset { assets: Wild(AllCounted(1)), beneficiary },
+		]);
+		assert_eq!(sent_xcm(), vec![(dest, expected)]);
+		assert_eq!(Balances::free_balance(&account), INITIAL_BALANCE - DeliveryFee::get());
+	});
+}
+
+#[test]
+fn claim_remote_requires_delivery_fees() {
+	new_test_ext().execute_with(|| {
+		let account: AccountId32 = [1; 32].into();
+		let assets: Assets = vec![(Parent, 10).into()].into();
+
+		assert_noop!(
+			ActivityPallet::claim_remote(
+				RuntimeOrigin::signed(account),
+				Parent.into(),
+				assets,
+				Here.into(),
+				0,
+				Here.into(),
+			),
+			crate::Error::<Test>::FeesNotMet
+		);
+		assert!(sent_xcm().is_empty());
+	});
+}
+
+#[test]
+fn claim_remote_rejects_bad_fee_asset() {
+	new_test_ext().execute_with(|| {
+		let account: AccountId32 = [0; 32].into();
+		let assets: Assets = vec![(Parent, 10).into()].into();
+
+		assert_noop!(
+			ActivityPallet::claim_remote(
+				RuntimeOrigin::signed(account),
+				Parent.into(),
+				assets,
+				Here.into(),
+				1,
+				Here.into(),
+			),
+			crate::Error::<Test>::BadFeeAsset
+		);
+		assert!(sent_xcm().is_empty());
+	});
+}
+
+#[test]
+fn trap_index_follows_traps_and_claims() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(System::block_number() + 1);
+		let account: AccountId32 = [0; 32].into();
+		let origin: Location =
+			Junction::AccountId32 { network: None, id: account.clone().into() }.into();
+		let assets: Assets = vec![(Here, 10).into()].into();
+		let hash = crate::trap_hash(&origin, &assets);
+
+		assert_ok!(ActivityPallet::trap_assets(
+			RuntimeOrigin::signed(account.clone()),
+			assets.clone()
+		));
+		assert_eq!(
+			ActivityPallet::trap_index(hash),
+			Some(TrapRecord { count: 1, details: Some((origin.into(), assets.clone().into())) })
+		);
+
+		assert_ok!(ActivityPallet::claim_assets(
+			RuntimeOrigin::signed(account),
+			assets,
+			Here.into()
+		));
+		assert_eq!(ActivityPallet::trap_index(hash), None);
+	});
+}
+
+#[test]
+fn trap_index_follows_traps_of_other_messages() {
+	new_test_ext().execute_with(|| {
+		let origin: Location = Junction::AccountId32 { network: None, id: [0; 32] }.into();
+		let assets: Assets = vec![(Here, 10).into()].into();
+		let message = Xcm(vec![
+			UnpaidExecution { weight_limit: Unlimited, check_origin: None },
+			WithdrawAsset(assets.clone()),
+		]);
+
+		let outcome = XcmExecutor::<XcmConfig>::execute_xcm(origin, message, [0; 32], Weight::MAX);
+		assert_ok!(outcome.ensure_complete());
+
+		assert_eq!(
+			ActivityPallet::trap_index(crate::trap_hash(&origin, &assets)),
+			Some(TrapRecord { count: 1, details: Some((origin.into(), assets.into())) })
+		);
+	});
+}
+
+#[test]
+fn trap_index_follows_claims_with_versioned_tickets() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(System::block_number() + 1);
+		let account: AccountId32 = [0; 32].into();
+		let origin: Location =
+			Junction::AccountId32 { network: None, id: account.clone().into() }.into();
+		let assets: Assets = vec![(Here, 10).into()].into();
+		let ticket: Location = X1(GeneralIndex(2)).into();
+		let hash = crate::claim_hash(&origin, &ticket, &assets).unwrap();
+		assert_ne!(hash, crate::trap_hash(&origin, &assets));
+
+		// Assets trapped back when the runtime used XCM v2, then indexed by the migration.
+		put_storage_value(b"PalletXcm", b"AssetTraps", hash.as_ref(), 1u32);
+		crate::TrapIndex::<Test>::insert(hash, TrapRecord { count: 1, details: None });
+
+		assert_ok!(ActivityPallet::claim_assets(
+			RuntimeOrigin::signed(account.clone()),
+			assets,
+			ticket
+		));
+		assert_eq!(ActivityPallet::trap_index(hash), None);
+		assert_eq!(Balances::free_balance(&account), INITIAL_BALANCE + 10);
+	});
+}
+
+#[test]
+fn migration_to_v1_backfills_trap_index() {
+	new_test_ext().execute_with(|| {
+		let origin: Location = Parent.into();
+		let context = XcmContext::with_message_id([0; 32]);
+		for amount in [10u128, 10, 20] {
+			let assets: Assets = vec![(Here, amount).into()].into();
+			PalletXcm::drop_assets(&origin, assets.into(), &context);
+		}
+		StorageVersion::new(0).put::<ActivityPallet>();
+
+		#[cfg(feature = "try-runtime")]
+		let state = MigrateToV1::<Test>::pre_upgrade().unwrap();
+		MigrateToV1::<Test>::on_runtime_upgrade();
+		#[cfg(feature = "try-runtime")]
+		assert_ok!(MigrateToV1::<Test>::post_upgrade(state));
+
+		assert_eq!(ActivityPallet::on_chain_storage_version(), 1);
+		let ten: Assets = vec![(Here, 10).into()].into();
+		assert_eq!(
+			ActivityPallet::trap_index(crate::trap_hash(&origin, &ten)),
+			Some(TrapRecord { count: 2, details: None })
+		);
+		assert_eq!(crate::TrapIndex::<Test>::iter().count(), 2);
+	});
+}
+
+#[cfg(feature = "try-runtime")]
+#[test]
+fn migration_to_v1_rejects_stale_index_entries() {
+	new_test_ext().execute_with(|| {
+		let origin: Location = Parent.into();
+		let assets: Assets = vec![(Here, 10).into()].into();
+		let context = XcmContext::with_message_id([0; 32]);
+		PalletXcm::drop_assets(&origin, assets.into(), &context);
+		StorageVersion::new(0).put::<ActivityPallet>();
+
+		// An entry left behind before the upgrade, for a trap that no longer exists.
+		let stale: Assets = vec![(Here, 20).into()].into();
+		crate::TrapIndex::<Test>::insert(
+			crate::trap_hash(&origin, &stale),
+			TrapRecord { count: 1, details: None },
+		);
+
+		let state = MigrateToV1::<Test>::pre_upgrade().unwrap();
+		MigrateToV1::<Test>::on_runtime_upgrade();
+		assert!(MigrateToV1::<Test>::post_upgrade(state).is_err());
+	});
+}
@@ -0,0 +1,322 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Runs the benchmarks of `pallet_activity_runtime_xcm` on the simulator parachain and prints
+//! its `weights.rs` from the results:
+//!
+//! ```text
+//! cargo run --release -p xcm-simulator-pba --example activity_weights \
+//!     --features activity-pallet,runtime-benchmarks > in-the-runtime/src/weights.rs
+//! ```
+//!
+//! This repository has no node for the `benchmark pallet` command, so the benchmarks run natively
+//! on a benchmarking state backend, the way the command samples and analyses them.
+
+use frame_benchmarking::{
+	Analysis, BenchmarkMetadata, BenchmarkParameter, BenchmarkResult, BenchmarkSelector,
+	Benchmarking,
+};
+use frame_support::traits::{StorageInfo, StorageInfoTrait, WhitelistedStorageKeys};
+use sc_client_db::BenchmarkingState;
+use sp_runtime::{traits::BlakeTwo256, BuildStorage};
+use sp_state_machine::{Ext, OverlayedChanges};
+use std::collections::BTreeMap;
+use xcm_simulator_pba::{
+	parachain::{ActivityPallet, AllPalletsWithSystem, MsgQueue, Runtime, System},
+	ALICE, INITIAL_BALANCE,
+};
+
+/// Values sampled per component, with the other components at their highest.
+const STEPS: u32 = 50;
+/// Runs per sampled set of component values.
+const REPEAT: u32 = 20;
+
+type Block = frame_system::mocking::MockBlock<Runtime>;
+
+/// The analysis of one benchmark.
+struct Benchmark {
+	name: String,
+	components: Vec<(BenchmarkParameter, u32, u32)>,
+	time: Analysis,
+	reads: Analysis,
+	writes: Analysis,
+	proof_size: Analysis,
+	/// Storage items accessed at the highest component values, with their key reads and writes.
+	storage: Vec<(String, Option<StorageInfo>, u32, u32)>,
+}
+
+fn main() {
+	let state = BenchmarkingState::<Block>::new(genesis(), None, true, true)
+		.expect("the genesis storage is valid");
+	let benchmarks: Vec<_> = ActivityPallet::benchmarks(false)
+		.into_iter()
+		.map(|metadata| benchmark(&state, metadata))
+		.collect();
+	print!("{}", weights(&benchmarks));
+}
+
+/// The parachain's storage as `para_ext` builds it.
+fn genesis() -> sp_runtime::Storage {
+	let mut storage = frame_system::GenesisConfig::<Runtime>::default().build_storage().unwrap();
+	pallet_balances::GenesisConfig::<Runtime> { balances: vec![(ALICE, INITIAL_BALANCE)] }
+		.assimilate_storage(&mut storage)
+		.unwrap();
+	storage
+}
+
+/// Runs the benchmark `name` once with the given component values, leaving `state` as it was.
+fn run(
+	state: &BenchmarkingState<Block>,
+	name: &[u8],
+	components: &[(BenchmarkParameter, u32)],
+	verify: bool,
+) -> Vec<BenchmarkResult> {
+	let whitelist = AllPalletsWithSystem::whitelisted_storage_keys();
+	let mut overlay = OverlayedChanges::<BlakeTwo256>::default();
+	let mut ext = Ext::new(&mut overlay, state, None);
+	sp_externalities::set_and_run_with_externalities(&mut ext, || {
+		System::set_block_number(1);
+		MsgQueue::set_para_id(1.into());
+		ActivityPallet::run_benchmark(name, components, &whitelist, verify, 1)
+	})
+	.unwrap_or_else(|error| {
+		panic!("benchmark {} failed: {:?}", String::from_utf8_lossy(name), error)
+	})
+}
+
+/// Samples every component from its lowest to its highest value and analyses the results.
+fn benchmark(state: &BenchmarkingState<Block>, metadata: BenchmarkMetadata) -> Benchmark {
+	let BenchmarkMetadata { name, components, .. } = metadata;
+	let highest: Vec<_> = components.iter().map(|&(param, _, high)| (param, high)).collect();
+
+	let mut samples = vec![highest.clone()];
+	for (index, &(_, low, high)) in components.iter().enumerate() {
+		let step = (high - low) as f32 / (STEPS - 1) as f32;
+		for s in 0..STEPS {
+			let mut sample = highest.clone();
+			sample[index].1 = ((low as f32 + step * s as f32) as u32).clamp(low, high);
+			samples.push(sample);
+		}
+	}
+	samples.dedup();
+
+	let mut results = Vec::new();
+	for sample in &samples {
+		run(state, &name, sample, true);
+		for _ in 0..REPEAT {
+			results.extend(run(state, &name, sample, false));
+		}
+	}
+
+	let analysis = |selector| Analysis::min_squares_iqr(&results, selector).unwrap();
+	let at_highest = results
+		.iter()
+		.find(|result| result.components == highest)
+		.expect("sampled first");
+	Benchmark {
+		name: String::from_utf8(name).unwrap(),
+		components,
+		time: analysis(BenchmarkSelector::ExtrinsicTime),
+		reads: analysis(BenchmarkSelector::Reads),
+		writes: analysis(BenchmarkSelector::Writes),
+		proof_size: analysis(BenchmarkSelector::ProofSize),
+		storage: storage(at_highest),
+	}
+}
+
+/// The storage items `result` read or wrote keys of, leaving out the whitelisted keys.
+fn storage(result: &BenchmarkResult) -> Vec<(String, Option<StorageInfo>, u32, u32)> {
+	let infos = AllPalletsWithSystem::storage_info();
+	let mut storage = BTreeMap::<Vec<u8>, (String, Option<StorageInfo>, u32, u32)>::new();
+	for (key, reads, writes, whitelisted) in &result.keys {
+		if *whitelisted {
+			continue
+		}
+		let prefix = key[..key.len().min(32)].to_vec();
+		let entry = storage.entry(prefix.clone()).or_insert_with(|| {
+			match infos.iter().find(|info| info.prefix.starts_with(&prefix)) {
+				Some(info) => (
+					format!(
+						"{}::{}",
+						String::from_utf8_lossy(&info.pallet_name),
+						String::from_utf8_lossy(&info.storage_name)
+					),
+					Some(info.clone()),
+					0,
+					0,
+				),
+				None => (format!("UNKNOWN KEY 0x{}", hex(key)), None, 0, 0),
+			}
+		});
+		entry.2 += u32::from(*reads > 0);
+		entry.3 += u32::from(*writes > 0);
+	}
+	storage.into_values().collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Formats `value` with an underscore every three digits.
+fn underscore(value: u128) -> String {
+	let digits = value.to_string();
+	let mut formatted = String::new();
+	for (index, digit) in digits.chars().enumerate() {
+		if index > 0 && (digits.len() - index).is_multiple_of(3) {
+			formatted.push('_');
+		}
+		formatted.push(digit);
+	}
+	formatted
+}
+
+/// The slopes of `analysis` that aren't zero, with their component names.
+fn slopes(analysis: &Analysis) -> Vec<(u128, &str)> {
+	analysis
+		.slopes
+		.iter()
+		.zip(&analysis.names)
+		.filter(|(slope, _)| **slope != 0)
+		.map(|(slope, name)| (*slope, name.as_str()))
+		.collect()
+}
+
+/// The arguments of the weight function of `benchmark`, prefixing the unused ones with `_` if
+/// `unused`.
+fn arguments(benchmark: &Benchmark, unused: bool) -> String {
+	benchmark
+		.components
+		.iter()
+		.map(|(param, _, _)| {
+			let name = format!("{:?}", param);
+			let used =
+				[&benchmark.time, &benchmark.reads, &benchmark.writes, &benchmark.proof_size]
+					.iter()
+					.any(|analysis| slopes(analysis).iter().any(|(_, used)| *used == name));
+			match unused && !used {
+				true => format!("_{}: u32, ", name),
+				false => format!("{}: u32, ", name),
+			}
+		})
+		.collect()
+}
+
+/// The body of the weight function of `benchmark`, with `db` the database weights.
+fn weight_fn(benchmark: &Benchmark, db: &str) -> String {
+	let mut f = String::new();
+	for (name, info, reads, writes) in &benchmark.storage {
+		f += &format!("\t/// Storage: `{}` (r:{} w:{})\n", name, reads, writes);
+		if let Some(info) = info {
+			f += &format!(
+				"\t/// Proof: `{}` (`max_values`: {:?}, `max_size`: {:?}, mode: `Measured`)\n",
+				name, info.max_values, info.max_size
+			);
+		}
+	}
+	for (param, low, high) in &benchmark.components {
+		f += &format!("\t/// The range of component `{:?}` is `[{}, {}]`.\n", param, low, high);
+	}
+	f += &format!("\tfn {}({}) -> Weight {{\n", benchmark.name, arguments(benchmark, true));
+	f += "\t\t// Proof Size summary in bytes:\n";
+	f += &format!("\t\t//  Measured:  `{}", benchmark.proof_size.base);
+	for (slope, name) in slopes(&benchmark.proof_size) {
+		f += &format!(" + {} * ({})", name, slope);
+	}
+	f += "`\n";
+	f += &format!(
+		"\t\t// Minimum execution time: {} picoseconds.\n",
+		underscore(benchmark.time.minimum * 1000)
+	);
+	f += &format!(
+		"\t\tWeight::from_parts({}, {})\n",
+		underscore(benchmark.time.base),
+		benchmark.proof_size.base
+	);
+	for (slope, name) in slopes(&benchmark.time) {
+		f += &format!(
+			"\t\t\t.saturating_add(Weight::from_parts({}, 0).saturating_mul({}.into()))\n",
+			underscore(slope),
+			name
+		);
+	}
+	for (analysis, kind) in [(&benchmark.reads, "reads"), (&benchmark.writes, "writes")] {
+		if analysis.base != 0 {
+			f += &format!("\t\t\t.saturating_add({}.{}({}_u64))\n", db, kind, analysis.base);
+		}
+		for (slope, name) in slopes(analysis) {
+			f += &format!(
+				"\t\t\t.saturating_add({}.{}(({}_u64).saturating_mul({}.into())))\n",
+				db, kind, slope, name
+			);
+		}
+	}
+	for (slope, name) in slopes(&benchmark.proof_size) {
+		f += &format!(
+			"\t\t\t.saturating_add(Weight::from_parts(0, {}).saturating_mul({}.into()))\n",
+			slope, name
+		);
+	}
+	f += "\t}\n";
+	f
+}
+
+/// The `weights.rs` of the pallet.
+fn weights(benchmarks: &[Benchmark]) -> String {
+	let mut f = String::new();
+	f += "// TODO license_header\n";
+	f += "//! Weights for `pallet_activity_runtime_xcm`.\n";
+	f += "//!\n";
+	f += "//! Generated by the `activity_weights` example of `xcm-simulator-pba`, which runs the\n";
+	f += "//! benchmarks natively on the simulator parachain:\n";
+	f += "//! `cargo run --release -p xcm-simulator-pba --example activity_weights --features activity-pallet,runtime-benchmarks`\n";
+	f += "//!\n";
+	f += &format!("//! STEPS: `{}`, REPEAT: `{}`\n", STEPS, REPEAT);
+	f += "//!\n";
+	f += "//! The times are native, the storage is in memory and the proof sizes are the measured ones\n";
+	f += "//! rather than worst case estimates, so a production runtime should regenerate the weights\n";
+	f += "//! with the `benchmark pallet` command of its node.\n";
+	f += "\n";
+	f += "#![cfg_attr(rustfmt, rustfmt_skip)]\n";
+	f += "#![allow(unused_parens)]\n";
+	f += "#![allow(unused_imports)]\n";
+	f += "\n";
+	f += "use frame_support::{traits::Get, weights::{Weight, constants::RocksDbWeight}};\n";
+	f += "use core::marker::PhantomData;\n";
+	f += "\n";
+	f += "/// Weight functions needed for `pallet_activity_runtime_xcm`.\n";
+	f += "pub trait WeightInfo {\n";
+	for benchmark in benchmarks {
+		f += &format!("\tfn {}({}) -> Weight;\n", benchmark.name, arguments(benchmark, false));
+	}
+	f += "}\n";
+	f += "\n";
+	f += "/// Weights for `pallet_activity_runtime_xcm` measured on the simulator parachain, with the runtime's database weights.\n";
+	f += "pub struct SubstrateWeight<T>(PhantomData<T>);\n";
+	f += "impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {\n";
+	for benchmark in benchmarks {
+		f += &weight_fn(benchmark, "T::DbWeight::get()");
+	}
+	f += "}\n";
+	f += "\n";
+	f += "// For backwards compatibility and tests, using the RocksDB database weights.\n";
+	f += "impl WeightInfo for () {\n";
+	for benchmark in benchmarks {
+		f += &weight_fn(benchmark, "RocksDbWeight::get()");
+	}
+	f += "}\n";
+	f
+}
@@ -0,0 +1,140 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Rules for `AliasOrigin`, which swaps the origin of a message for one it may act as.
+//!
+//! Both mocks let any origin alias a location within it, such as a para aliasing one of its
+//! accounts with [`AliasChildLocation`]. Paras also trust the location set with
+//! [`para_trust_aliaser`], none by default, to alias any account on them with
+//! [`TrustedAliaserCase`], acting as a proxy for accounts of other chains.
+
+use core::marker::PhantomData;
+use frame_support::traits::{ContainsPair, Get};
+use xcm::latest::prelude::*;
+
+use crate::{parachain, TestExt};
+
+/// Lets any origin alias a location within it.
+pub struct AliasChildLocation;
+impl ContainsPair<MultiLocation, MultiLocation> for AliasChildLocation {
+	fn contains(origin: &MultiLocation, target: &MultiLocation) -> bool {
+		target.starts_with(origin)
+	}
+}
+
+/// Lets the location `T` returns, if any, alias any account on this chain.
+pub struct TrustedAliaserCase<T>(PhantomData<T>);
+impl<T: Get<Option<MultiLocation>>> ContainsPair<MultiLocation, MultiLocation>
+	for TrustedAliaserCase<T>
+{
+	fn contains(origin: &MultiLocation, target: &MultiLocation) -> bool {
+		T::get().as_ref() == Some(origin) &&
+			matches!(target, MultiLocation { parents: 0, interior: X1(AccountId32 { .. }) })
+	}
+}
+
+/// Trusts `aliaser` to alias any account on `Para`, or no one if `None`.
+pub fn para_trust_aliaser<Para: TestExt>(aliaser: Option<MultiLocation>) {
+	Para::execute_with(|| parachain::TrustedAliaser::set(&aliaser));
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		MockNet, ParaA, ParaB, ParachainPalletBalances, ParachainPalletXcm, ALICE, BOB,
+		INITIAL_BALANCE,
+	};
+	use frame_support::assert_ok;
+	use parachain::{mock_msg_queue::Event as MsgQueueEvent, RuntimeEvent};
+
+	fn account(id: sp_runtime::AccountId32) -> Junction {
+		AccountId32 { network: None, id: id.into() }
+	}
+
+	fn send_to_para_b(message: Vec<Instruction<()>>) {
+		ParaA::execute_with(|| {
+			assert_ok!(ParachainPalletXcm::send_xcm(Here, (Parent, Parachain(2)), Xcm(message)));
+		});
+	}
+
+	fn para_b_outcome() -> Option<XcmError> {
+		ParaB::execute_with(|| {
+			parachain::para_events().iter().find_map(|event| match event {
+				RuntimeEvent::MsgQueue(MsgQueueEvent::Success(_)) => Some(None),
+				RuntimeEvent::MsgQueue(MsgQueueEvent::Fail(_, error)) => Some(Some(*error)),
+				_ => None,
+			})
+		})
+		.expect("ParaB received the message")
+	}
+
+	#[test]
+	fn para_aliases_its_account() {
+		MockNet::reset();
+
+		let alice_on_a = MultiLocation::new(1, X2(Parachain(1), account(ALICE)));
+		send_to_para_b(vec![AliasOrigin(alice_on_a), ExpectOrigin(Some(alice_on_a))]);
+
+		assert_eq!(para_b_outcome(), None);
+	}
+
+	#[test]
+	fn para_cannot_alias_another_para() {
+		MockNet::reset();
+
+		let alice_on_c = MultiLocation::new(1, X2(Parachain(3), account(ALICE)));
+		send_to_para_b(vec![AliasOrigin(alice_on_c)]);
+
+		assert_eq!(para_b_outcome(), Some(XcmError::NoPermission));
+	}
+
+	#[test]
+	fn trusted_para_acts_as_local_account() {
+		MockNet::reset();
+		para_trust_aliaser::<ParaB>(Some((Parent, Parachain(1)).into()));
+
+		let amount = 100;
+		send_to_para_b(vec![
+			AliasOrigin(account(ALICE).into()),
+			WithdrawAsset((Parent, amount).into()),
+			DepositAsset { assets: AllCounted(1).into(), beneficiary: account(BOB).into() },
+		]);
+
+		assert_eq!(para_b_outcome(), None);
+		ParaB::execute_with(|| {
+			assert_eq!(ParachainPalletBalances::free_balance(ALICE), INITIAL_BALANCE - amount);
+			assert_eq!(ParachainPalletBalances::free_balance(BOB), amount);
+		});
+	}
+
+	#[test]
+	fn untrusted_para_cannot_alias_local_account() {
+		MockNet::reset();
+		para_trust_aliaser::<ParaB>(Some((Parent, Parachain(3)).into()));
+
+		send_to_para_b(vec![
+			AliasOrigin(account(ALICE).into()),
+			WithdrawAsset((Parent, 100).into()),
+			DepositAsset { assets: AllCounted(1).into(), beneficiary: account(BOB).into() },
+		]);
+
+		assert_eq!(para_b_outcome(), Some(XcmError::NoPermission));
+		ParaB::execute_with(|| {
+			assert_eq!(ParachainPalletBalances::free_balance(ALICE), INITIAL_BALANCE);
+		});
+	}
+}
@@ -0,0 +1,400 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Bridges `MockNet`, a Kusama network, with a Polkadot network through an in-memory bridge.
+//!
+//! Each network has a bridge hub with para id [`BRIDGE_HUB_ID`]. The Polkadot network only
+//! consists of [`PolkadotBridgeHub`], which runs the parachain runtime outside of `MockNet`. Its
+//! router skips `MockNet` with [`MockNetRouter`], so it only reaches other chains over the bridge.
+//!
+//! Chains send messages to a `GlobalConsensus` destination through their bridge hub with
+//! [`ExportRouter`]. The bridge hub exports them with [`InMemoryBridge`], which prefixes them with
+//! `UniversalOrigin` and `DescendOrigin` to keep their origin, and queues them until
+//! [`dispatch_bridged_messages`] hands them to the bridge hub on the other side. That bridge hub
+//! executes messages for itself and sends the others on to their destination, which accepts the
+//! bridged origin from its bridge hub through `UniversalAliases`.
+
+use codec::Encode;
+use core::marker::PhantomData;
+use frame_support::{traits::Get, weights::Weight};
+use std::{cell::RefCell, collections::VecDeque};
+use xcm::latest::prelude::*;
+use xcm_executor::{traits::ExportXcm, XcmExecutor};
+
+use crate::{
+	parachain, relay_chain, KusamaBridgeHub, MockNet, PolkadotBridgeHub, TestExt, BRIDGE_HUB_ID,
+};
+
+/// A message exported by a bridge hub, waiting to be dispatched on the other side.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BridgedMessage {
+	/// The network the message is sent to.
+	pub network: NetworkId,
+	/// The destination of the message within `network`.
+	pub destination: InteriorMultiLocation,
+	/// The message, prefixed with its origin.
+	pub message: Xcm<()>,
+}
+
+/// What became of a bridged message on the other side.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Dispatched {
+	/// The receiving bridge hub executed the message itself.
+	Executed(Outcome),
+	/// The receiving bridge hub sent the message on to its destination.
+	Routed(XcmHash),
+	/// The message cannot reach its destination.
+	Failed(SendError),
+}
+
+std::thread_local! {
+	static BRIDGED_MESSAGES: RefCell<VecDeque<BridgedMessage>> = const { RefCell::new(VecDeque::new()) };
+}
+
+/// Resets `MockNet`, [`PolkadotBridgeHub`] and the messages still on the bridge.
+pub fn reset() {
+	MockNet::reset();
+	PolkadotBridgeHub::reset_ext();
+	BRIDGED_MESSAGES.with(|messages| messages.borrow_mut().clear());
+}
+
+/// Dispatches the messages exported so far on the bridge hub of their destination's network.
+pub fn dispatch_bridged_messages() -> Vec<(BridgedMessage, Dispatched)> {
+	let messages: Vec<_> =
+		BRIDGED_MESSAGES.with(|messages| messages.borrow_mut().drain(..).collect());
+	messages
+		.into_iter()
+		.map(|message| {
+			let dispatched = match message.network {
+				NetworkId::Kusama => KusamaBridgeHub::execute_with(|| dispatch(&message, true)),
+				NetworkId::Polkadot =>
+					PolkadotBridgeHub::execute_with(|| dispatch(&message, false)),
+				_ => Dispatched::Failed(SendError::Unroutable),
+			};
+			(message, dispatched)
+		})
+		.collect()
+}
+
+/// Dispatches `message` on the bridge hub whose externalities we're in, sending it on within the
+/// network if `can_route`.
+fn dispatch(message: &BridgedMessage, can_route: bool) -> Dispatched {
+	let xcm = message.message.clone();
+	if message.destination == X1(Parachain(BRIDGE_HUB_ID)) {
+		let id = xcm.using_encoded(sp_io::hashing::blake2_256);
+		return Dispatched::Executed(XcmExecutor::<parachain::XcmConfig>::execute_xcm(
+			Here,
+			xcm.into(),
+			id,
+			Weight::MAX,
+		))
+	}
+	if !can_route {
+		return Dispatched::Failed(SendError::Unroutable)
+	}
+	// The bridge hub is a para, one level below the destination's interior.
+	match send_xcm::<parachain::XcmRouter>(MultiLocation::new(1, message.destination), xcm) {
+		Ok((hash, _)) => Dispatched::Routed(hash),
+		Err(error) => Dispatched::Failed(error),
+	}
+}
+
+/// Exports messages to `BridgedNetwork` over the in-memory bridge.
+pub struct InMemoryBridge<BridgedNetwork>(PhantomData<BridgedNetwork>);
+
+impl<BridgedNetwork: Get<NetworkId>> ExportXcm for InMemoryBridge<BridgedNetwork> {
+	type Ticket = BridgedMessage;
+
+	fn validate(
+		network: NetworkId,
+		_channel: u32,
+		universal_source: &mut Option<InteriorMultiLocation>,
+		destination: &mut Option<InteriorMultiLocation>,
+		message: &mut Option<Xcm<()>>,
+	) -> SendResult<BridgedMessage> {
+		if network != BridgedNetwork::get() {
+			return Err(SendError::NotApplicable)
+		}
+		let universal_source = universal_source.take().ok_or(SendError::MissingArgument)?;
+		let destination = destination.take().ok_or(SendError::MissingArgument)?;
+		let message = message.take().ok_or(SendError::MissingArgument)?;
+
+		let (source_network, source) =
+			universal_source.split_global().map_err(|()| SendError::Unroutable)?;
+		let mut prefixed = vec![UniversalOrigin(GlobalConsensus(source_network))];
+		if source != Here {
+			prefixed.push(DescendOrigin(source));
+		}
+		prefixed.extend(message.0);
+
+		Ok((BridgedMessage { network, destination, message: Xcm(prefixed) }, MultiAssets::new()))
+	}
+
+	fn deliver(message: BridgedMessage) -> Result<XcmHash, SendError> {
+		let hash = message.message.using_encoded(sp_io::hashing::blake2_256);
+		BRIDGED_MESSAGES.with(|messages| messages.borrow_mut().push_back(message));
+		Ok(hash)
+	}
+}
+
+/// Sends messages with `Router` only from chains of `MockNet`'s network, as the chains of other
+/// networks aren't part of `MockNet`.
+pub struct MockNetRouter<Network, Router>(PhantomData<(Network, Router)>);
+
+impl<Network: Get<NetworkId>, Router: SendXcm> SendXcm for MockNetRouter<Network, Router> {
+	type Ticket = Router::Ticket;
+
+	fn validate(
+		destination: &mut Option<MultiLocation>,
+		message: &mut Option<Xcm<()>>,
+	) -> SendResult<Self::Ticket> {
+		if Network::get() != relay_chain::KusamaNetwork::get() {
+			return Err(SendError::NotApplicable)
+		}
+		Router::validate(destination, message)
+	}
+
+	fn deliver(ticket: Self::Ticket) -> Result<XcmHash, SendError> {
+		Router::deliver(ticket)
+	}
+}
+
+/// Sends messages for other consensus networks to `BridgeHub`, wrapped in `ExportMessage`, or
+/// exports them with `Exporter` when this chain is the bridge hub.
+pub struct ExportRouter<UniversalLocation, BridgeHub, Router, Exporter>(
+	PhantomData<(UniversalLocation, BridgeHub, Router, Exporter)>,
+);
+
+/// How an [`ExportRouter`] sends a message on.
+pub enum ExportTicket<Remote, Local> {
+	/// To the bridge hub, asking it to export the message.
+	Remote(Remote),
+	/// Over the bridge, from the bridge hub itself.
+	Local(Local),
+}
+
+impl<
+		UniversalLocation: Get<InteriorMultiLocation>,
+		BridgeHub: Get<MultiLocation>,
+		Router: SendXcm,
+		Exporter: ExportXcm,
+	> SendXcm for ExportRouter<UniversalLocation, BridgeHub, Router, Exporter>
+{
+	type Ticket = ExportTicket<Router::Ticket, Exporter::Ticket>;
+
+	fn validate(
+		destination: &mut Option<MultiLocation>,
+		message: &mut Option<Xcm<()>>,
+	) -> SendResult<Self::Ticket> {
+		let universal_location = UniversalLocation::get();
+		let dest = destination.ok_or(SendError::MissingArgument)?;
+		let (network, remote) = match dest.interior.split_first() {
+			(remote, Some(GlobalConsensus(network)))
+				if dest.parents as usize == universal_location.len() &&
+					universal_location.global_consensus() != Ok(network) =>
+				(network, remote),
+			_ => return Err(SendError::NotApplicable),
+		};
+		let xcm = message.clone().ok_or(SendError::MissingArgument)?;
+
+		let bridge_hub = BridgeHub::get();
+		let validated = if bridge_hub == Here.into() {
+			let (ticket, price) = Exporter::validate(
+				network,
+				0,
+				&mut Some(universal_location),
+				&mut Some(remote),
+				&mut Some(xcm),
+			)?;
+			(ExportTicket::Local(ticket), price)
+		} else {
+			let export = Xcm(vec![ExportMessage { network, destination: remote, xcm }]);
+			let (ticket, price) = Router::validate(&mut Some(bridge_hub), &mut Some(export))?;
+			(ExportTicket::Remote(ticket), price)
+		};
+		// Only consume the arguments once the message is sure to go, so that the routers after
+		// this one still get them.
+		*destination = None;
+		*message = None;
+		Ok(validated)
+	}
+
+	fn deliver(ticket: Self::Ticket) -> Result<XcmHash, SendError> {
+		match ticket {
+			ExportTicket::Remote(ticket) => Router::deliver(ticket),
+			ExportTicket::Local(ticket) => Exporter::deliver(ticket),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{ParaA, ParachainPalletXcm};
+	use frame_support::assert_ok;
+
+	fn bridged(network: NetworkId, para: u32) -> MultiLocation {
+		MultiLocation::new(2, X2(GlobalConsensus(network), Parachain(para)))
+	}
+
+	#[test]
+	fn para_reaches_bridged_bridge_hub() {
+		reset();
+
+		let origin = bridged(NetworkId::Kusama, 1);
+		ParaA::execute_with(|| {
+			assert_ok!(ParachainPalletXcm::send_xcm(
+				Here,
+				bridged(NetworkId::Polkadot, BRIDGE_HUB_ID),
+				Xcm(vec![ExpectOrigin(Some(origin))]),
+			));
+		});
+
+		let dispatched = dispatch_bridged_messages();
+		assert_eq!(dispatched.len(), 1);
+		let (message, dispatched) = &dispatched[0];
+		assert_eq!(message.network, NetworkId::Polkadot);
+		assert_eq!(
+			message.message.0[..2],
+			[UniversalOrigin(GlobalConsensus(NetworkId::Kusama)), DescendOrigin(X1(Parachain(1)))]
+		);
+		assert!(matches!(dispatched, Dispatched::Executed(Outcome::Complete(_))));
+		assert!(dispatch_bridged_messages().is_empty());
+	}
+
+	#[test]
+	fn bridged_bridge_hub_reaches_para() {
+		reset();
+
+		let origin = bridged(NetworkId::Polkadot, BRIDGE_HUB_ID);
+		PolkadotBridgeHub::execute_with(|| {
+			assert_ok!(send_xcm::<parachain::XcmRouter>(
+				bridged(NetworkId::Kusama, 1),
+				Xcm(vec![ExpectOrigin(Some(origin))]),
+			));
+		});
+
+		let dispatched = dispatch_bridged_messages();
+		assert!(matches!(dispatched[..], [(_, Dispatched::Routed(_))]));
+		ParaA::execute_with(|| {
+			assert!(parachain::para_events().iter().any(|event| matches!(
+				event,
+				parachain::RuntimeEvent::MsgQueue(parachain::mock_msg_queue::Event::Success(_))
+			)));
+		});
+	}
+
+	/// Accepts any message, recording that the routers before it left it the arguments.
+	struct AcceptAll;
+
+	impl SendXcm for AcceptAll {
+		type Ticket = ();
+
+		fn validate(
+			destination: &mut Option<MultiLocation>,
+			message: &mut Option<Xcm<()>>,
+		) -> SendResult<()> {
+			destination.take().ok_or(SendError::MissingArgument)?;
+			message.take().ok_or(SendError::MissingArgument)?;
+			Ok(((), MultiAssets::new()))
+		}
+
+		fn deliver(_: ()) -> Result<XcmHash, SendError> {
+			Ok([0; 32])
+		}
+	}
+
+	#[test]
+	fn export_router_leaves_unbridged_networks_to_other_routers() {
+		reset();
+
+		type Routers = (
+			ExportRouter<
+				parachain::UniversalLocation,
+				parachain::BridgeHub,
+				AcceptAll,
+				InMemoryBridge<parachain::BridgedNetwork>,
+			>,
+			AcceptAll,
+		);
+		KusamaBridgeHub::execute_with(|| {
+			assert_ok!(send_xcm::<Routers>(bridged(NetworkId::Westend, 1), Xcm(vec![ClearOrigin])));
+		});
+		assert!(dispatch_bridged_messages().is_empty());
+	}
+
+	#[test]
+	fn bridged_bridge_hub_cannot_reach_mock_net_directly() {
+		reset();
+
+		PolkadotBridgeHub::execute_with(|| {
+			assert_eq!(
+				send_xcm::<parachain::XcmRouter>((Parent, Parachain(1)).into(), Xcm(vec![])),
+				Err(SendError::NotApplicable)
+			);
+			assert_eq!(
+				send_xcm::<parachain::XcmRouter>(Parent.into(), Xcm(vec![])),
+				Err(SendError::NotApplicable)
+			);
+		});
+
+		assert!(dispatch_bridged_messages().is_empty());
+		ParaA::execute_with(|| {
+			assert!(!parachain::para_events()
+				.iter()
+				.any(|event| matches!(event, parachain::RuntimeEvent::MsgQueue(_))));
+		});
+	}
+
+	#[test]
+	fn bridged_network_only_has_its_bridge_hub() {
+		reset();
+
+		ParaA::execute_with(|| {
+			assert_ok!(ParachainPalletXcm::send_xcm(
+				Here,
+				bridged(NetworkId::Polkadot, 2000),
+				Xcm(vec![ClearOrigin]),
+			));
+		});
+
+		let dispatched = dispatch_bridged_messages();
+		assert!(matches!(dispatched[..], [(_, Dispatched::Failed(SendError::Unroutable))]));
+	}
+
+	#[test]
+	fn only_bridge_hub_can_claim_bridged_origin() {
+		reset();
+
+		ParaA::execute_with(|| {
+			assert_ok!(ParachainPalletXcm::send_xcm(
+				Here,
+				(Parent, Parachain(2)),
+				Xcm(vec![UniversalOrigin(GlobalConsensus(NetworkId::Polkadot))]),
+			));
+		});
+
+		crate::ParaB::execute_with(|| {
+			assert!(parachain::para_events().iter().any(|event| matches!(
+				event,
+				parachain::RuntimeEvent::MsgQueue(parachain::mock_msg_queue::Event::Fail(
+					_,
+					XcmError::InvalidLocation
+				))
+			)));
+		});
+	}
+}
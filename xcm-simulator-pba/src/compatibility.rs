@@ -0,0 +1,198 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Checks how an XCM program survives being sent at every XCM version `MockNet` supports.
+//!
+//! The program is converted to each version and converted back to compare it with the original.
+//! `ParaA` then advertises the version, the relay learns it by subscribing, and sends the program
+//! down through its [`VersionedRouter`](crate::versioning::VersionedRouter), so `ParaA` executes
+//! what a chain of that version receives. Versions are taken from `staging-xcm`, so a new version
+//! is covered as soon as the dependency knows it. `staging-xcm` 6 stops at v3, so v4 isn't covered.
+
+use core::fmt;
+use xcm::{
+	latest::{prelude::*, send_xcm},
+	IntoVersion, Version as XcmVersion, VersionedXcm,
+};
+
+use crate::{
+	parachain::{self, mock_msg_queue},
+	relay_chain,
+	replay::ReplayOutcome,
+	versioning::relay_subscribe,
+	MockNet, ParaA, Relay, TestExt,
+};
+
+/// The oldest XCM version chains still accept.
+pub const OLDEST_VERSION: XcmVersion = 2;
+
+/// What converting a program to a version did to it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Conversion {
+	/// Converting back gives the original program.
+	Lossless,
+	/// Converting back gives a different program, e.g. a weight without its proof size.
+	Lossy(Xcm<()>),
+	/// The program cannot be expressed in the version, e.g. it uses newer instructions.
+	Failed,
+}
+
+/// How a program fared at one version.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Compatibility {
+	pub version: XcmVersion,
+	pub conversion: Conversion,
+	/// How `ParaA` executed the program sent at the version, unless the conversion failed.
+	pub outcome: Option<ReplayOutcome>,
+}
+
+impl fmt::Display for Compatibility {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let conversion = match &self.conversion {
+			Conversion::Lossless => "lossless",
+			Conversion::Lossy(_) => "lossy",
+			Conversion::Failed => "failed",
+		};
+		write!(f, "v{}: {}", self.version, conversion)?;
+		if let Some(outcome) = &self.outcome {
+			write!(f, ", {:?}", outcome)?;
+		}
+		Ok(())
+	}
+}
+
+/// Converts `xcm` to every supported version and sends it at each of them from the relay to `ParaA`
+/// on a fresh `MockNet`, with `ParaA` advertising that version.
+///
+/// Resets `MockNet` for every version, so it doesn't depend on nor keep any earlier state.
+pub fn check_versions(xcm: Xcm<()>) -> Vec<Compatibility> {
+	(OLDEST_VERSION..=xcm::latest::VERSION)
+		.map(|version| check_version(&xcm, version))
+		.collect()
+}
+
+fn check_version(xcm: &Xcm<()>, version: XcmVersion) -> Compatibility {
+	let converted = match VersionedXcm::from(xcm.clone()).into_version(version) {
+		Ok(converted) => converted,
+		Err(()) => return Compatibility { version, conversion: Conversion::Failed, outcome: None },
+	};
+	let conversion = match Xcm::<()>::try_from(converted.clone()) {
+		Ok(back) if back == *xcm => Conversion::Lossless,
+		Ok(back) => Conversion::Lossy(back),
+		Err(()) => Conversion::Failed,
+	};
+
+	MockNet::reset();
+	ParaA::execute_with(|| parachain::XcmVersioner::set_version(version));
+	relay_subscribe(1);
+	let emitted_before = ParaA::execute_with(|| parachain::para_events().len());
+	Relay::execute_with(|| {
+		send_xcm::<relay_chain::XcmRouter>(Parachain(1).into(), xcm.clone())
+			.expect("the program converts to the version it is sent at");
+	});
+
+	Compatibility { version, conversion, outcome: downward_outcome(emitted_before) }
+}
+
+/// How `ParaA` handled the last downward message it received after its first `emitted_before`
+/// events.
+fn downward_outcome(emitted_before: usize) -> Option<ReplayOutcome> {
+	ParaA::execute_with(|| {
+		parachain::para_events().into_iter().skip(emitted_before).rev().find_map(
+			|event| match event {
+				parachain::RuntimeEvent::MsgQueue(mock_msg_queue::Event::InvalidFormat(_)) =>
+					Some(ReplayOutcome::InvalidFormat),
+				parachain::RuntimeEvent::MsgQueue(mock_msg_queue::Event::UnsupportedVersion(_)) =>
+					Some(ReplayOutcome::UnsupportedVersion),
+				parachain::RuntimeEvent::MsgQueue(mock_msg_queue::Event::ExecutedDownward(
+					_,
+					outcome,
+				)) => Some(ReplayOutcome::Executed(outcome)),
+				_ => None,
+			},
+		)
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::versioning::take_sent_messages;
+	use frame_support::weights::Weight;
+
+	fn conversions(xcm: Xcm<()>) -> Vec<(XcmVersion, Conversion)> {
+		check_versions(xcm)
+			.into_iter()
+			.map(|checked| (checked.version, checked.conversion))
+			.collect()
+	}
+
+	#[test]
+	fn common_instructions_work_at_every_version() {
+		let checked = check_versions(Xcm(vec![ClearOrigin]));
+
+		assert_eq!(checked.len(), (xcm::latest::VERSION - OLDEST_VERSION + 1) as usize);
+		for compatibility in checked {
+			assert_eq!(compatibility.conversion, Conversion::Lossless, "{}", compatibility);
+			let complete = matches!(
+				compatibility.outcome,
+				Some(ReplayOutcome::Executed(Outcome::Complete(_)))
+			);
+			assert!(complete, "{}", compatibility);
+		}
+	}
+
+	#[test]
+	fn programs_are_sent_at_the_checked_version() {
+		let xcm = Xcm(vec![ClearOrigin]);
+		take_sent_messages();
+
+		let compatibility = check_version(&xcm, 2);
+
+		assert!(matches!(
+			compatibility.outcome,
+			Some(ReplayOutcome::Executed(Outcome::Complete(_)))
+		));
+		let expected = VersionedXcm::from(xcm).into_version(2).unwrap();
+		assert!(take_sent_messages()
+			.iter()
+			.any(|sent| sent.destination == Parachain(1).into() && sent.message == expected));
+	}
+
+	#[test]
+	fn newer_instructions_fail_on_older_versions() {
+		assert_eq!(
+			conversions(Xcm(vec![ClearTopic])),
+			vec![(2, Conversion::Failed), (3, Conversion::Lossless)]
+		);
+	}
+
+	#[test]
+	fn proof_size_is_lost_on_v2() {
+		let xcm = Xcm(vec![BuyExecution {
+			fees: (Here, 1u128).into(),
+			weight_limit: Limited(Weight::from_parts(1_000, 1)),
+		}]);
+
+		match &conversions(xcm)[..] {
+			[(2, Conversion::Lossy(back)), (3, Conversion::Lossless)] => assert!(matches!(
+				back.0[..],
+				[BuyExecution { weight_limit: Limited(weight), .. }] if weight.ref_time() == 1_000
+			)),
+			other => panic!("unexpected conversions: {:?}", other),
+		}
+	}
+}
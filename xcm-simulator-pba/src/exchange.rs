@@ -0,0 +1,155 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Sets up the parachain's `AssetPool`, which `ExchangeAsset` swaps through.
+//!
+//! Pools are constant product pools without fees. With `maximal`, `ExchangeAsset` gives all of
+//! `give` and fails unless it receives at least `want`, which bounds the slippage. Otherwise it
+//! receives exactly `want`, gives as little of `give` as that takes and keeps the rest in holding.
+//!
+//! The reserves are virtual. No account holds them, as a para has no balances of other chains'
+//! tokens to fund them from: the assets received are minted into holding and the assets given
+//! are burnt, so the total issuance of the relay token on ParaA changes with each swap.
+
+use xcm::latest::prelude::*;
+
+use crate::{parachain, TestExt};
+
+/// Creates the pool on `Para` between the assets of `a` and `b`, with their amounts as virtual
+/// reserves.
+pub fn create_pool<Para: TestExt>(a: impl Into<MultiAsset>, b: impl Into<MultiAsset>) {
+	let (a, b) = (a.into(), b.into());
+	Para::execute_with(|| {
+		parachain::AssetPool::set_pool(a.id, fungible(&a), b.id, fungible(&b));
+	});
+}
+
+/// The reserves of `a` and `b` in the pool between them on `Para`, if there is one.
+pub fn pool_reserves<Para: TestExt>(
+	a: impl Into<AssetId>,
+	b: impl Into<AssetId>,
+) -> Option<(u128, u128)> {
+	let (a, b) = (a.into(), b.into());
+	Para::execute_with(|| {
+		parachain::AssetPool::reserves((a, b))
+			.or_else(|| parachain::AssetPool::reserves((b, a)).map(|(b, a)| (a, b)))
+	})
+}
+
+fn fungible(asset: &MultiAsset) -> u128 {
+	match asset.fun {
+		Fungible(amount) => amount,
+		NonFungible(_) => panic!("pools only hold fungible assets"),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		MockNet, ParaA, ParaB, ParachainPalletBalances, ParachainPalletXcm, ALICE, INITIAL_BALANCE,
+	};
+	use frame_support::assert_ok;
+
+	const PARA_B_TOKEN: (Parent, Junction) = (Parent, Parachain(2));
+
+	/// Sends `amount` of ParaB's token to ParaA, exchanging it there for the relay token to pay
+	/// for execution and depositing the relay tokens to ALICE.
+	fn swap_from_para_b(amount: u128, want: u128, maximal: bool) {
+		ParaB::execute_with(|| {
+			let message = Xcm(vec![
+				ReserveAssetDeposited((PARA_B_TOKEN, amount).into()),
+				ExchangeAsset {
+					give: Definite((PARA_B_TOKEN, amount).into()),
+					want: (Parent, want).into(),
+					maximal,
+				},
+				BuyExecution { fees: (Parent, want).into(), weight_limit: Unlimited },
+				DepositAsset {
+					assets: Wild(AllOf { id: Concrete(Parent.into()), fun: WildFungible }),
+					beneficiary: AccountId32 { network: None, id: ALICE.into() }.into(),
+				},
+			]);
+			assert_ok!(ParachainPalletXcm::send_xcm(Here, (Parent, Parachain(1)), message));
+		});
+	}
+
+	fn alice_on_para_a() -> u128 {
+		ParaA::execute_with(|| ParachainPalletBalances::free_balance(ALICE))
+	}
+
+	#[test]
+	fn pays_fees_with_foreign_asset() {
+		MockNet::reset();
+		create_pool::<ParaA>((PARA_B_TOKEN, 1_000), (Parent, 1_000));
+		let issuance = ParaA::execute_with(ParachainPalletBalances::total_issuance);
+
+		swap_from_para_b(100, 90, true);
+
+		// 1_000 * 100 / (1_000 + 100) = 90.9, rounded down.
+		assert_eq!(alice_on_para_a(), INITIAL_BALANCE + 90);
+		// The reserves are virtual, so the relay tokens received are minted.
+		assert_eq!(ParaA::execute_with(ParachainPalletBalances::total_issuance), issuance + 90);
+		assert_eq!(pool_reserves::<ParaA>(PARA_B_TOKEN, Parent), Some((1_100, 910)));
+	}
+
+	#[test]
+	fn slippage_beyond_want_fails() {
+		MockNet::reset();
+		create_pool::<ParaA>((PARA_B_TOKEN, 1_000), (Parent, 1_000));
+
+		swap_from_para_b(100, 95, true);
+
+		assert_eq!(alice_on_para_a(), INITIAL_BALANCE);
+		assert_eq!(pool_reserves::<ParaA>(PARA_B_TOKEN, Parent), Some((1_000, 1_000)));
+	}
+
+	#[test]
+	fn non_maximal_gives_only_what_it_takes() {
+		MockNet::reset();
+		// The pool works in either order.
+		create_pool::<ParaA>((Parent, 1_000), (PARA_B_TOKEN, 1_000));
+
+		swap_from_para_b(100, 50, false);
+
+		// 1_000 * 50 / (1_000 - 50) = 52.6, rounded up. The other 47 stay in holding.
+		assert_eq!(alice_on_para_a(), INITIAL_BALANCE + 50);
+		assert_eq!(pool_reserves::<ParaA>(PARA_B_TOKEN, Parent), Some((1_053, 950)));
+	}
+
+	#[test]
+	fn overflowing_reserves_fail() {
+		MockNet::reset();
+		let half = 1u128 << 127;
+		create_pool::<ParaA>((PARA_B_TOKEN, half), (Parent, 2));
+
+		// Takes `half` for 1, which would leave `u128::MAX + 1` in the pool.
+		swap_from_para_b(half, 1, false);
+
+		assert_eq!(alice_on_para_a(), INITIAL_BALANCE);
+		assert_eq!(pool_reserves::<ParaA>(PARA_B_TOKEN, Parent), Some((half, 2)));
+	}
+
+	#[test]
+	fn no_pool_no_deal() {
+		MockNet::reset();
+
+		swap_from_para_b(100, 1, true);
+
+		assert_eq!(alice_on_para_a(), INITIAL_BALANCE);
+		assert_eq!(pool_reserves::<ParaA>(PARA_B_TOKEN, Parent), None);
+	}
+}
@@ -14,17 +14,32 @@
 // You should have received a copy of the GNU General Public License
 // along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
 
+pub mod aliasing;
+pub mod bridge;
+pub mod compatibility;
+pub mod exchange;
+pub mod locking;
+pub mod mock_version_changer;
 pub mod parachain;
+pub mod query;
 pub mod relay_chain;
+pub mod replay;
+pub mod versioning;
+
+#[cfg(all(test, feature = "activity-pallet"))]
+mod tests;
 
 use polkadot_primitives::Id as ParaId;
 use sp_runtime::{traits::AccountIdConversion, BuildStorage};
+use xcm::latest::NetworkId;
 pub use xcm_simulator::TestExt;
 use xcm_simulator::{decl_test_network, decl_test_parachain, decl_test_relay_chain};
 
 pub const ALICE: sp_runtime::AccountId32 = sp_runtime::AccountId32::new([0u8; 32]);
 pub const BOB: sp_runtime::AccountId32 = sp_runtime::AccountId32::new([5u8; 32]);
 pub const INITIAL_BALANCE: u128 = 1_000_000_000;
+/// The para id of the bridge hub of both networks, see [`bridge`].
+pub const BRIDGE_HUB_ID: u32 = 1002;
 
 decl_test_parachain! {
 	pub struct ParaA {
@@ -44,6 +59,24 @@ decl_test_parachain! {
 	}
 }
 
+decl_test_parachain! {
+	pub struct KusamaBridgeHub {
+		Runtime = parachain::Runtime,
+		XcmpMessageHandler = parachain::MsgQueue,
+		DmpMessageHandler = parachain::MsgQueue,
+		new_ext = para_ext(BRIDGE_HUB_ID),
+	}
+}
+
+decl_test_parachain! {
+	pub struct PolkadotBridgeHub {
+		Runtime = parachain::Runtime,
+		XcmpMessageHandler = parachain::MsgQueue,
+		DmpMessageHandler = parachain::MsgQueue,
+		new_ext = bridge_hub_ext(NetworkId::Polkadot),
+	}
+}
+
 decl_test_relay_chain! {
 	pub struct Relay {
 		Runtime = relay_chain::Runtime,
@@ -62,6 +95,7 @@ decl_test_network! {
 		parachains = vec![
 			(1, ParaA),
 			(2, ParaB),
+			(1002, KusamaBridgeHub),
 		],
 	}
 }
@@ -87,6 +121,13 @@ pub fn para_ext(para_id: u32) -> sp_io::TestExternalities {
 	ext
 }
 
+/// The bridge hub of `network`, outside of `MockNet` unless `network` is Kusama.
+pub fn bridge_hub_ext(network: NetworkId) -> sp_io::TestExternalities {
+	let mut ext = para_ext(BRIDGE_HUB_ID);
+	ext.execute_with(|| parachain::MsgQueue::set_network(network));
+	ext
+}
+
 pub fn relay_ext() -> sp_io::TestExternalities {
 	use relay_chain::{Runtime, System};
 
@@ -119,3 +160,5 @@ pub type RelayChainPalletXcm = pallet_xcm::Pallet<relay_chain::Runtime>;
 pub type RelayChainPalletBalances = pallet_balances::Pallet<relay_chain::Runtime>;
 pub type ParachainPalletXcm = pallet_xcm::Pallet<parachain::Runtime>;
 pub type ParachainPalletBalances = pallet_balances::Pallet<parachain::Runtime>;
+#[cfg(feature = "activity-pallet")]
+pub type ParachainActivityPallet = pallet_activity_runtime_xcm::Pallet<parachain::Runtime>;
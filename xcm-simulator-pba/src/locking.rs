@@ -0,0 +1,167 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Inspects assets locked across chains with `LockAsset`.
+//!
+//! The chain holding the asset locks the owner's balance under [`LOCK_ID`] and sends
+//! `NoteUnlockable` to the unlocker, which records what it may unlock if it trusts the locker.
+//! The relay locks its token for paras, and paras trust the relay as the locker of it. The relay in
+//! turn trusts ParaA to lock the relay token it holds. The unlocker releases the lock with
+//! `UnlockAsset`, or on the owner's `RequestUnlock`.
+
+use frame_support::traits::LockIdentifier;
+use xcm::latest::prelude::*;
+use xcm_executor::traits::AssetLock;
+
+use crate::{parachain, relay_chain, ParachainPalletXcm, Relay, RelayChainPalletXcm, TestExt};
+
+/// The id `pallet_xcm` locks balances under.
+pub const LOCK_ID: LockIdentifier = *b"py/xcmlk";
+
+/// The balance of `account` on the relay locked by `LockAsset`.
+pub fn relay_locked(account: &relay_chain::AccountId) -> relay_chain::Balance {
+	Relay::execute_with(|| locked::<relay_chain::Runtime>(account))
+}
+
+/// The balance of `account` on `Para` locked by `LockAsset`.
+pub fn para_locked<Para: TestExt>(account: &parachain::AccountId) -> parachain::Balance {
+	Para::execute_with(|| locked::<parachain::Runtime>(account))
+}
+
+fn locked<T: pallet_balances::Config>(account: &T::AccountId) -> T::Balance {
+	pallet_balances::Pallet::<T>::locks(account)
+		.iter()
+		.filter(|lock| lock.id == LOCK_ID)
+		.map(|lock| lock.amount)
+		.fold(Default::default(), |total, amount| total + amount)
+}
+
+/// Whether `Para` may unlock `asset` locked by `locker` for `owner`, as noted by
+/// `NoteUnlockable` and not yet reduced by `RequestUnlock`.
+pub fn para_can_unlock<Para: TestExt>(
+	locker: impl Into<MultiLocation>,
+	asset: impl Into<MultiAsset>,
+	owner: impl Into<MultiLocation>,
+) -> bool {
+	let (locker, asset, owner) = (locker.into(), asset.into(), owner.into());
+	Para::execute_with(|| {
+		ParachainPalletXcm::prepare_reduce_unlockable(locker, asset, owner).is_ok()
+	})
+}
+
+/// Whether the relay may unlock `asset` locked by `locker` for `owner`, as noted by
+/// `NoteUnlockable` and not yet reduced by `RequestUnlock`.
+pub fn relay_can_unlock(
+	locker: impl Into<MultiLocation>,
+	asset: impl Into<MultiAsset>,
+	owner: impl Into<MultiLocation>,
+) -> bool {
+	let (locker, asset, owner) = (locker.into(), asset.into(), owner.into());
+	Relay::execute_with(|| {
+		RelayChainPalletXcm::prepare_reduce_unlockable(locker, asset, owner).is_ok()
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{para_account_id, MockNet, ParaA, ParaB, ALICE};
+	use frame_support::{assert_ok, weights::Weight};
+	use xcm::VersionedXcm;
+
+	#[test]
+	fn lock_on_relay_and_request_unlock() {
+		MockNet::reset();
+
+		let amount = 100u128;
+		let owner = (Parent, Parachain(1));
+
+		// ParaA locks part of its sovereign account on the relay, with ParaB as the unlocker.
+		ParaA::execute_with(|| {
+			let message = Xcm(vec![LockAsset {
+				asset: (Here, amount).into(),
+				unlocker: Parachain(2).into(),
+			}]);
+			assert_ok!(ParachainPalletXcm::send_xcm(Here, Parent, message));
+		});
+
+		assert_eq!(relay_locked(&para_account_id(1)), amount);
+		assert!(para_can_unlock::<ParaB>(Parent, (Parent, amount), owner));
+		assert!(!para_can_unlock::<ParaB>(Parent, (Parent, amount + 1), owner));
+
+		// ParaA asks ParaB to release part of the lock.
+		ParaA::execute_with(|| {
+			let message = Xcm(vec![RequestUnlock {
+				asset: (Parent, amount - 40).into(),
+				locker: Parent.into(),
+			}]);
+			assert_ok!(ParachainPalletXcm::send_xcm(Here, (Parent, Parachain(2)), message));
+		});
+
+		assert_eq!(relay_locked(&para_account_id(1)), 40);
+		assert!(para_can_unlock::<ParaB>(Parent, (Parent, 40), owner));
+		assert!(!para_can_unlock::<ParaB>(Parent, (Parent, 41), owner));
+	}
+
+	#[test]
+	fn only_the_relay_is_trusted_to_lock_its_token() {
+		MockNet::reset();
+
+		let amount = 100u128;
+
+		// ParaA claims to have locked relay tokens for itself.
+		ParaA::execute_with(|| {
+			let message = Xcm(vec![NoteUnlockable {
+				asset: (Parent, amount).into(),
+				owner: (Parent, Parachain(1)).into(),
+			}]);
+			assert_ok!(ParachainPalletXcm::send_xcm(Here, (Parent, Parachain(2)), message));
+		});
+
+		let para_a = (Parent, Parachain(1));
+		assert!(!para_can_unlock::<ParaB>(para_a, (Parent, amount), para_a));
+	}
+
+	#[test]
+	fn lock_on_para() {
+		MockNet::reset();
+
+		let amount = 100u128;
+
+		// ALICE locks her balance on ParaA, with the relay as the unlocker.
+		ParaA::execute_with(|| {
+			let message: Xcm<parachain::RuntimeCall> =
+				Xcm(vec![LockAsset { asset: (Parent, amount).into(), unlocker: Parent.into() }]);
+			assert_ok!(ParachainPalletXcm::execute(
+				parachain::RuntimeOrigin::signed(ALICE),
+				Box::new(VersionedXcm::V3(message)),
+				Weight::from_parts(100_000_000_000, 100_000_000_000)
+			));
+		});
+
+		assert_eq!(para_locked::<ParaA>(&ALICE), amount);
+		assert_eq!(para_locked::<ParaB>(&ALICE), 0);
+
+		let alice = ParaA::execute_with(|| AccountId32 {
+			network: Some(parachain::RelayNetwork::get()),
+			id: ALICE.into(),
+		});
+		let owner = (Parachain(1), alice);
+		assert!(relay_can_unlock(Parachain(1), (Here, amount), owner));
+		assert!(!relay_can_unlock(Parachain(1), (Here, amount + 1), owner));
+		assert!(!relay_can_unlock(Parachain(2), (Here, amount), owner));
+	}
+}
@@ -20,7 +20,7 @@ use codec::{Decode, Encode};
 use core::marker::PhantomData;
 use frame_support::{
 	construct_runtime, parameter_types,
-	traits::{ContainsPair, Everything, Nothing},
+	traits::{ContainsPair, Equals, Everything, Nothing},
 	weights::Weight,
 };
 use frame_system::EnsureRoot;
@@ -35,16 +35,24 @@ use frame_support::derive_impl;
 use polkadot_core_primitives::BlockNumber as RelayBlockNumber;
 use polkadot_primitives::Id as ParaId;
 use polkadot_parachain_primitives::primitives::{
-	DmpMessageHandler, XcmpMessageFormat, XcmpMessageHandler,
+	DmpMessageHandler, Sibling, XcmpMessageFormat, XcmpMessageHandler,
 };
-use xcm::{latest::prelude::*, Version as XcmVersion, VersionedXcm};
+use xcm::{latest::prelude::*, VersionedXcm};
 use xcm_builder::{
 	AllowUnpaidExecutionFrom, CurrencyAdapter as XcmCurrencyAdapter, DescribeAccountId32Terminal,
 	EnsureXcmOrigin, FixedRateOfFungible, FixedWeightBounds, HashedDescription, IsConcrete,
-	NativeAsset, SignedAccountId32AsNative, SignedToAccountId32, AccountId32Aliases,
+	NativeAsset, SiblingParachainConvertsVia, SignedAccountId32AsNative, SignedToAccountId32,
+	AccountId32Aliases,
 };
 use xcm_executor::{Config, XcmExecutor};
 
+use crate::{
+	aliasing::{AliasChildLocation, TrustedAliaserCase},
+	bridge::{ExportRouter, InMemoryBridge, MockNetRouter},
+	versioning::VersionedRouter,
+	BRIDGE_HUB_ID,
+};
+
 pub type AccountId = AccountId32;
 pub type Balance = u128;
 
@@ -92,12 +100,27 @@ impl pallet_balances::Config for Runtime {
 
 parameter_types! {
 	pub const TokenLocation: MultiLocation = MultiLocation::parent();
-	pub const RelayNetwork: NetworkId = NetworkId::Kusama;
-	pub UniversalLocation: InteriorMultiLocation = RelayNetwork::get().into();
+	pub RelayNetwork: NetworkId = MsgQueue::network();
+	pub UniversalLocation: InteriorMultiLocation =
+		X2(GlobalConsensus(RelayNetwork::get()), Parachain(MsgQueue::parachain_id().into()));
+}
+
+parameter_types! {
+	pub BridgedNetwork: NetworkId = match RelayNetwork::get() {
+		NetworkId::Kusama => NetworkId::Polkadot,
+		_ => NetworkId::Kusama,
+	};
+	pub BridgeHub: MultiLocation = match u32::from(MsgQueue::parachain_id()) {
+		BRIDGE_HUB_ID => Here.into(),
+		_ => (Parent, Parachain(BRIDGE_HUB_ID)).into(),
+	};
+	pub BridgedNetworkViaBridgeHub: (MultiLocation, Junction) =
+		(BridgeHub::get(), GlobalConsensus(BridgedNetwork::get()));
 }
 
 pub type LocationToAccountId = (
 	AccountId32Aliases<RelayNetwork, AccountId>,
+	SiblingParachainConvertsVia<Sibling, AccountId>,
 	HashedDescription<AccountId, DescribeAccountId32Terminal>, // Legacy support
 );
 
@@ -112,14 +135,85 @@ parameter_types! {
 pub type LocalAssetTransactor =
 	XcmCurrencyAdapter<Balances, IsConcrete<TokenLocation>, LocationToAccountId, AccountId, ()>;
 
-pub type XcmRouter = super::ParachainXcmRouter<MsgQueue>;
+/// Lets anyone withdraw and deposit the fungible assets at `GeneralIndex` junctions, without
+/// keeping any balance, so the activity pallet can be benchmarked with many distinct assets.
+#[cfg(all(feature = "activity-pallet", feature = "runtime-benchmarks"))]
+pub struct IndexedAssetTransactor;
+#[cfg(all(feature = "activity-pallet", feature = "runtime-benchmarks"))]
+impl IndexedAssetTransactor {
+	fn is_indexed(what: &MultiAsset) -> bool {
+		matches!(
+			what,
+			MultiAsset {
+				id: Concrete(MultiLocation { parents: 0, interior: X1(GeneralIndex(_)) }),
+				fun: Fungible(_),
+			}
+		)
+	}
+}
+
+#[cfg(all(feature = "activity-pallet", feature = "runtime-benchmarks"))]
+impl xcm_executor::traits::TransactAsset for IndexedAssetTransactor {
+	fn deposit_asset(
+		what: &MultiAsset,
+		_who: &MultiLocation,
+		_context: Option<&XcmContext>,
+	) -> XcmResult {
+		Self::is_indexed(what).then_some(()).ok_or(XcmError::AssetNotFound)
+	}
+
+	fn withdraw_asset(
+		what: &MultiAsset,
+		_who: &MultiLocation,
+		_maybe_context: Option<&XcmContext>,
+	) -> Result<xcm_executor::Assets, XcmError> {
+		Self::is_indexed(what)
+			.then(|| what.clone().into())
+			.ok_or(XcmError::AssetNotFound)
+	}
+}
+
+#[cfg(all(feature = "activity-pallet", feature = "runtime-benchmarks"))]
+pub type AssetTransactors = (LocalAssetTransactor, IndexedAssetTransactor);
+#[cfg(not(all(feature = "activity-pallet", feature = "runtime-benchmarks")))]
+pub type AssetTransactors = LocalAssetTransactor;
+
+parameter_types! {
+	pub storage TrustedAliaser: Option<MultiLocation> = None;
+}
+
+pub type Aliasers = (AliasChildLocation, TrustedAliaserCase<TrustedAliaser>);
+
+pub type XcmRouter = VersionedRouter<
+	PolkadotXcm,
+	(
+		MockNetRouter<RelayNetwork, super::ParachainXcmRouter<MsgQueue>>,
+		ExportRouter<
+			UniversalLocation,
+			BridgeHub,
+			super::ParachainXcmRouter<MsgQueue>,
+			InMemoryBridge<BridgedNetwork>,
+		>,
+	),
+>;
 pub type Barrier = AllowUnpaidExecutionFrom<Everything>;
 
+/// The activity pallet, when installed, wraps the asset trap and claims of `pallet_xcm` to index
+/// the traps.
+#[cfg(feature = "activity-pallet")]
+pub type AssetTrap = ActivityPallet;
+#[cfg(not(feature = "activity-pallet"))]
+pub type AssetTrap = PolkadotXcm;
+#[cfg(feature = "activity-pallet")]
+pub type AssetClaims = ActivityPallet;
+#[cfg(not(feature = "activity-pallet"))]
+pub type AssetClaims = PolkadotXcm;
+
 pub struct XcmConfig;
 impl Config for XcmConfig {
 	type RuntimeCall = RuntimeCall;
 	type XcmSender = XcmRouter;
-	type AssetTransactor = LocalAssetTransactor;
+	type AssetTransactor = AssetTransactors;
 	type OriginConverter = XcmOriginToCallOrigin;
 	type IsReserve = NativeAsset;
 	type IsTeleporter = ();
@@ -128,19 +222,19 @@ impl Config for XcmConfig {
 	type Weigher = FixedWeightBounds<UnitWeightCost, RuntimeCall, MaxInstructions>;
 	type Trader = FixedRateOfFungible<TokensPerSecond, ()>;
 	type ResponseHandler = PolkadotXcm;
-	type AssetTrap = PolkadotXcm;
-	type AssetLocker = ();
-	type AssetExchanger = ();
-	type AssetClaims = PolkadotXcm;
+	type AssetTrap = AssetTrap;
+	type AssetLocker = PolkadotXcm;
+	type AssetExchanger = AssetPool;
+	type AssetClaims = AssetClaims;
 	type SubscriptionService = PolkadotXcm;
-	type PalletInstancesInfo = ();
+	type PalletInstancesInfo = AllPalletsWithSystem;
 	type FeeManager = ();
 	type MaxAssetsIntoHolding = ConstU32<64>;
-	type MessageExporter = ();
-	type UniversalAliases = Nothing;
+	type MessageExporter = InMemoryBridge<BridgedNetwork>;
+	type UniversalAliases = Equals<BridgedNetworkViaBridgeHub>;
 	type CallDispatcher = RuntimeCall;
 	type SafeCallFilter = Everything;
-	type Aliasers = ();
+	type Aliasers = Aliasers;
 }
 
 #[frame_support::pallet]
@@ -165,6 +259,16 @@ pub mod mock_msg_queue {
 	#[pallet::getter(fn parachain_id)]
 	pub(super) type ParachainId<T: Config> = StorageValue<_, ParaId, ValueQuery>;
 
+	#[pallet::type_value]
+	pub fn DefaultNetwork() -> NetworkId {
+		NetworkId::Kusama
+	}
+
+	#[pallet::storage]
+	#[pallet::getter(fn network)]
+	/// The consensus network of the relay chain this para is attached to.
+	pub(super) type Network<T: Config> = StorageValue<_, NetworkId, ValueQuery, DefaultNetwork>;
+
 	#[pallet::storage]
 	#[pallet::getter(fn received_dmp)]
 	/// A queue of received DMP messages
@@ -205,6 +309,10 @@ pub mod mock_msg_queue {
 			ParachainId::<T>::put(para_id);
 		}
 
+		pub fn set_network(network: NetworkId) {
+			Network::<T>::put(network);
+		}
+
 		fn handle_xcmp_message(
 			sender: ParaId,
 			_sent_at: RelayBlockNumber,
@@ -323,23 +431,83 @@ impl pallet_xcm::Config for Runtime {
 	const VERSION_DISCOVERY_QUEUE_SIZE: u32 = 100;
 	type AdvertisedXcmVersion = XcmVersioner;
 	type Currency = Balances;
-	type CurrencyMatcher = ();
+	type CurrencyMatcher = IsConcrete<TokenLocation>;
 	type TrustedLockers = TrustedLockers;
 	type SovereignAccountOf = LocationToAccountId;
 	type MaxLockers = ConstU32<8>;
-	type MaxRemoteLockConsumers = ConstU32<0>;
+	type MaxRemoteLockConsumers = ConstU32<16>;
 	type RemoteLockConsumerIdentifier = ();
 	type WeightInfo = pallet_xcm::TestWeightInfo;
-	#[cfg(feature = "runtime-benchmarks")]
-	type ReachableDest = ReachableDest;
 	type AdminOrigin = EnsureRoot<AccountId>;
 }
 
-// Pallet to provide the version, used to test runtime upgrade version changes
+// Pallet receiving the responses of queries registered with `pallet_xcm::new_notify_query`
+#[frame_support::pallet]
+pub mod mock_query_notifier {
+	use super::*;
+	use frame_support::pallet_prelude::*;
+	use frame_system::pallet_prelude::*;
+	use xcm::latest::QueryId;
+
+	#[pallet::config]
+	pub trait Config: frame_system::Config + pallet_xcm::Config {
+		type RuntimeEvent: From<Event<Self>> + IsType<<Self as frame_system::Config>::RuntimeEvent>;
+		type RuntimeOrigin: IsType<<Self as frame_system::Config>::RuntimeOrigin>
+			+ Into<Result<pallet_xcm::Origin, <Self as Config>::RuntimeOrigin>>;
+	}
+
+	#[pallet::pallet]
+	#[pallet::without_storage_info]
+	pub struct Pallet<T>(_);
+
+	#[pallet::storage]
+	#[pallet::getter(fn received_responses)]
+	/// The responses notified so far, with the queries they answer
+	pub(super) type ReceivedResponses<T: Config> =
+		StorageValue<_, Vec<(QueryId, Response)>, ValueQuery>;
+
+	#[pallet::event]
+	#[pallet::generate_deposit(pub(super) fn deposit_event)]
+	pub enum Event<T: Config> {
+		/// The response to a query was received from the given responder.
+		ResponseReceived(MultiLocation, QueryId, Response),
+	}
+
+	#[pallet::call]
+	impl<T: Config> Pallet<T> {
+		/// Called by `pallet_xcm` with the response to a query.
+		///
+		/// Pass it to `new_notify_query` with any `query_id` and `response`, `pallet_xcm` fills
+		/// them in when the response arrives.
+		#[pallet::call_index(0)]
+		#[pallet::weight(Weight::from_parts(1_000_000, 1_000))]
+		pub fn notify(
+			origin: OriginFor<T>,
+			query_id: QueryId,
+			response: Response,
+		) -> DispatchResult {
+			let responder =
+				pallet_xcm::ensure_response(<T as Config>::RuntimeOrigin::from(origin))?;
+			<ReceivedResponses<T>>::append((query_id, response.clone()));
+			Self::deposit_event(Event::ResponseReceived(responder, query_id, response));
+			Ok(())
+		}
+	}
+}
+
+impl mock_query_notifier::Config for Runtime {
+	type RuntimeEvent = RuntimeEvent;
+	type RuntimeOrigin = RuntimeOrigin;
+}
+
+// Pallet exchanging assets held by XCM programs through constant product pools, without fees.
+// The reserves are virtual: no account holds them, so the assets received are minted into holding
+// and the assets given are burnt with it.
 #[frame_support::pallet]
-pub mod mock_version_changer {
+pub mod mock_asset_pool {
 	use super::*;
 	use frame_support::pallet_prelude::*;
+	use xcm_executor::{traits::AssetExchange, Assets};
 
 	#[pallet::config]
 	pub trait Config: frame_system::Config {
@@ -350,38 +518,154 @@ pub mod mock_version_changer {
 	impl<T: Config> Pallet<T> {}
 
 	#[pallet::pallet]
+	#[pallet::without_storage_info]
 	pub struct Pallet<T>(_);
 
 	#[pallet::storage]
-	#[pallet::getter(fn current_version)]
-	pub(super) type CurrentVersion<T: Config> = StorageValue<_, XcmVersion, ValueQuery>;
-
-	impl<T: Config> Get<XcmVersion> for Pallet<T> {
-		fn get() -> XcmVersion {
-			Self::current_version()
-		}
-	}
+	#[pallet::getter(fn reserves)]
+	/// The reserves of the pool between two assets, in the order of its key
+	pub(super) type Reserves<T: Config> =
+		StorageMap<_, Blake2_128Concat, (AssetId, AssetId), (u128, u128), OptionQuery>;
 
 	#[pallet::event]
 	#[pallet::generate_deposit(pub(super) fn deposit_event)]
 	pub enum Event<T: Config> {
-		// XCMP
-		/// Some XCM was executed OK.
-		VersionChanged(XcmVersion),
+		/// The first asset was given in exchange for the second.
+		Exchanged(MultiAsset, MultiAsset),
 	}
 
 	impl<T: Config> Pallet<T> {
-		pub fn set_version(version: XcmVersion) {
-			CurrentVersion::<T>::put(version);
-			Self::deposit_event(Event::VersionChanged(version));
+		/// Sets the virtual reserves of the pool between `a` and `b`, creating it if needed. No
+		/// balance backs them.
+		pub fn set_pool(a: AssetId, reserve_a: u128, b: AssetId, reserve_b: u128) {
+			Reserves::<T>::remove((b, a));
+			Reserves::<T>::insert((a, b), (reserve_a, reserve_b));
+		}
+
+		/// The reserves of `give` and `want` in the pool between them.
+		fn pool(give: AssetId, want: AssetId) -> Option<(u128, u128)> {
+			Self::reserves((give, want))
+				.or_else(|| Self::reserves((want, give)).map(|(want, give)| (give, want)))
+		}
+
+		fn update_pool(give: AssetId, reserve_give: u128, want: AssetId, reserve_want: u128) {
+			if Reserves::<T>::contains_key((give, want)) {
+				Reserves::<T>::insert((give, want), (reserve_give, reserve_want));
+			} else {
+				Reserves::<T>::insert((want, give), (reserve_want, reserve_give));
+			}
+		}
+
+		/// The amounts given and received exchanging at most `give` for at least `want`, and the
+		/// reserves after the exchange.
+		///
+		/// When `maximal`, all of `give` is exchanged for as much as possible. Otherwise exactly
+		/// `want` is received for as little as possible.
+		fn quote(
+			(reserve_give, reserve_want): (u128, u128),
+			give: u128,
+			want: u128,
+			maximal: bool,
+		) -> Option<((u128, u128), (u128, u128))> {
+			let (given, received) = if maximal {
+				let total = reserve_give.checked_add(give).filter(|t| *t > 0)?;
+				(give, reserve_want.checked_mul(give)? / total)
+			} else {
+				let remaining = reserve_want.checked_sub(want).filter(|r| *r > 0)?;
+				let numerator = reserve_give.checked_mul(want)?;
+				(numerator.checked_add(remaining - 1)? / remaining, want)
+			};
+			if given > give || received < want {
+				return None
+			}
+			let reserves = (reserve_give.checked_add(given)?, reserve_want.checked_sub(received)?);
+			Some(((given, received), reserves))
+		}
+	}
+
+	impl<T: Config> AssetExchange for Pallet<T> {
+		fn exchange_asset(
+			_origin: Option<&MultiLocation>,
+			give: Assets,
+			want: &MultiAssets,
+			maximal: bool,
+		) -> Result<Assets, Assets> {
+			let (give_id, give_amount) = match &give.fungible.iter().collect::<Vec<_>>()[..] {
+				[(id, amount)] if give.non_fungible.is_empty() => (**id, **amount),
+				_ => return Err(give),
+			};
+			let (want_id, want_amount) = match want.inner().as_slice() {
+				[MultiAsset { id, fun: Fungible(amount) }] => (*id, *amount),
+				_ => return Err(give),
+			};
+			let Some((reserve_give, reserve_want)) = Self::pool(give_id, want_id) else {
+				return Err(give)
+			};
+			let reserves = (reserve_give, reserve_want);
+			let Some(((given, received), (reserve_give, reserve_want))) =
+				Self::quote(reserves, give_amount, want_amount, maximal)
+			else {
+				return Err(give)
+			};
+
+			Self::update_pool(give_id, reserve_give, want_id, reserve_want);
+			Self::deposit_event(Event::Exchanged(
+				(give_id, given).into(),
+				(want_id, received).into(),
+			));
+
+			let mut exchanged = Assets::from(MultiAsset::from((want_id, received)));
+			if given < give_amount {
+				exchanged.subsume((give_id, give_amount - given).into());
+			}
+			Ok(exchanged)
 		}
 	}
 }
 
+impl mock_asset_pool::Config for Runtime {
+	type RuntimeEvent = RuntimeEvent;
+}
+
+pub use crate::mock_version_changer;
+
 impl mock_version_changer::Config for Runtime {
 	type RuntimeEvent = RuntimeEvent;
 }
 
+#[cfg(feature = "activity-pallet")]
+impl pallet_activity_runtime_xcm::Config for Runtime {
+	type RuntimeEvent = RuntimeEvent;
+	type XcmExecutor = XcmExecutor<XcmConfig>;
+	type LocationConverter = LocalOriginToLocation;
+	type AssetTrap = PolkadotXcm;
+	type AssetClaims = PolkadotXcm;
+	type XcmRouter = XcmRouter;
+	type MaxClaims = ConstU32<8>;
+	type WeightInfo = ();
+	#[cfg(feature = "runtime-benchmarks")]
+	type BenchmarkHelper = ActivityBenchmarkHelper;
+}
+
+/// Funds the account with the relay token and pads the assets with indexed ones.
+#[cfg(all(feature = "activity-pallet", feature = "runtime-benchmarks"))]
+pub struct ActivityBenchmarkHelper;
+#[cfg(all(feature = "activity-pallet", feature = "runtime-benchmarks"))]
+impl pallet_activity_runtime_xcm::BenchmarkHelper<AccountId> for ActivityBenchmarkHelper {
+	fn create_assets(who: &AccountId, n: u32) -> MultiAssets {
+		use frame_support::traits::Currency;
+
+		Balances::make_free_balance_be(who, crate::INITIAL_BALANCE);
+		let mut assets: Vec<MultiAsset> = vec![(Parent, crate::INITIAL_BALANCE / 2).into()];
+		assets.extend((1..n).map(|i| (X1(GeneralIndex(i as u128)), 1u128).into()));
+		assets.into()
+	}
+
+	fn reachable_dest() -> MultiLocation {
+		Parent.into()
+	}
+}
+
 type Block = frame_system::mocking::MockBlock<Runtime>;
 
 construct_runtime!(
@@ -391,6 +675,10 @@ construct_runtime!(
 		MsgQueue: mock_msg_queue::{Pallet, Storage, Event<T>},
 		PolkadotXcm: pallet_xcm::{Pallet, Call, Event<T>, Origin},
 		XcmVersioner: mock_version_changer::{Pallet, Storage, Event<T>},
+		QueryNotifier: mock_query_notifier::{Pallet, Call, Storage, Event<T>},
+		AssetPool: mock_asset_pool::{Pallet, Storage, Event<T>},
+		#[cfg(feature = "activity-pallet")]
+		ActivityPallet: pallet_activity_runtime_xcm::{Pallet, Call, Storage, Event<T>},
 	}
 );
 
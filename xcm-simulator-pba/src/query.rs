@@ -0,0 +1,293 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Registers queries with `pallet_xcm` and builds the instructions answering them.
+//!
+//! Queries are registered on the chain sending the message, inside its `execute_with`. Once
+//! `MockNet` delivered the message and the response, the response can be taken from the querying
+//! chain:
+//!
+//! ```ignore
+//! let query_id = ParaA::execute_with(|| {
+//!     let (query_id, report) = query::report_error::<parachain::Runtime>(Parent, Here);
+//!     assert_ok!(ParachainPalletXcm::send_xcm(Here, Parent, Xcm(vec![report])));
+//!     query_id
+//! });
+//! let response = ParaA::execute_with(|| query::take_response::<parachain::Runtime>(query_id));
+//! ```
+
+use frame_support::{dispatch::GetDispatchInfo, traits::Get, weights::Weight};
+use xcm::latest::{prelude::*, QueryId};
+use xcm_executor::traits::{QueryHandler, QueryResponseStatus};
+
+/// Blocks after which an unanswered query times out. `pallet_xcm` only records the timeout, the
+/// query stays pending past it.
+pub const QUERY_TIMEOUT: u32 = 100;
+
+/// Registers a query for a response from `responder` and returns where it should send it.
+///
+/// `interior` is the location on this chain the message asking for the response is sent from, as
+/// given to `pallet_xcm::Pallet::send_xcm`. The response is stored until taken with
+/// [`take_response`], or handed to `notify` if given.
+pub fn expect_response<T: pallet_xcm::Config>(
+	responder: impl Into<MultiLocation>,
+	interior: impl Into<Junctions>,
+	notify: Option<<T as pallet_xcm::Config>::RuntimeCall>,
+) -> QueryResponseInfo {
+	let responder = responder.into();
+	let querier = MultiLocation::from(interior.into());
+	let timeout = frame_system::Pallet::<T>::block_number() + QUERY_TIMEOUT.into();
+	let destination = T::UniversalLocation::get()
+		.invert_target(&responder)
+		.expect("MockNet chains can reach each other; qed");
+
+	let (query_id, max_weight) = match notify {
+		Some(notify) => {
+			let max_weight = notify.get_dispatch_info().weight;
+			let query_id =
+				pallet_xcm::Pallet::<T>::new_notify_query(responder, notify, timeout, querier);
+			(query_id, max_weight)
+		},
+		None => (pallet_xcm::Pallet::<T>::new_query(responder, timeout, querier), Weight::zero()),
+	};
+	QueryResponseInfo { destination, query_id, max_weight }
+}
+
+/// A `ReportHolding` making `responder` report the `assets` in its holding register.
+pub fn report_holding<T: pallet_xcm::Config>(
+	responder: impl Into<MultiLocation>,
+	interior: impl Into<Junctions>,
+	assets: MultiAssetFilter,
+) -> (QueryId, Instruction<()>) {
+	let response_info = expect_response::<T>(responder, interior, None);
+	(response_info.query_id, ReportHolding { response_info, assets })
+}
+
+/// A `ReportError` making `responder` report its error register.
+pub fn report_error<T: pallet_xcm::Config>(
+	responder: impl Into<MultiLocation>,
+	interior: impl Into<Junctions>,
+) -> (QueryId, Instruction<()>) {
+	let response_info = expect_response::<T>(responder, interior, None);
+	(response_info.query_id, ReportError(response_info))
+}
+
+/// A `QueryPallet` making `responder` report its instances of the pallet crate `module_name`, e.g.
+/// `pallet_balances`.
+pub fn query_pallet<T: pallet_xcm::Config>(
+	responder: impl Into<MultiLocation>,
+	interior: impl Into<Junctions>,
+	module_name: &str,
+) -> (QueryId, Instruction<()>) {
+	let response_info = expect_response::<T>(responder, interior, None);
+	let module_name = module_name.as_bytes().to_vec();
+	(response_info.query_id, QueryPallet { module_name, response_info })
+}
+
+/// Takes the response to the query `query_id`, if it arrived.
+pub fn take_response<T: pallet_xcm::Config>(query_id: QueryId) -> Option<Response> {
+	match <pallet_xcm::Pallet<T> as QueryHandler>::take_response(query_id) {
+		QueryResponseStatus::Ready { response, .. } => Some(response),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		para_account_id, parachain, relay_chain,
+		replay::{replay, Channel, ReplayOutcome},
+		MockNet, ParaA, ParachainPalletXcm, Relay, RelayChainPalletBalances, TestExt, ALICE,
+		INITIAL_BALANCE,
+	};
+	use codec::Encode;
+	use frame_support::{assert_ok, traits::PalletInfoAccess};
+	use xcm::{latest::PalletInfo, VersionedXcm};
+
+	/// Sends `message` from ParaA itself to the relay and returns the response to `query_id`.
+	fn ask_relay(message: impl FnOnce() -> (QueryId, Xcm<()>)) -> Option<Response> {
+		let query_id = ParaA::execute_with(|| {
+			let (query_id, message) = message();
+			assert_ok!(ParachainPalletXcm::send_xcm(Here, Parent, message));
+			query_id
+		});
+		ParaA::execute_with(|| take_response::<parachain::Runtime>(query_id))
+	}
+
+	#[test]
+	fn withdraw_and_report_holding() {
+		MockNet::reset();
+
+		let amount = 100u128;
+		let response = ask_relay(|| {
+			let (query_id, report) = report_holding::<parachain::Runtime>(Parent, Here, All.into());
+			let message = Xcm(vec![
+				WithdrawAsset((Here, amount).into()),
+				BuyExecution { fees: (Here, amount).into(), weight_limit: Unlimited },
+				report,
+				DepositAsset {
+					assets: All.into(),
+					beneficiary: AccountId32 { network: None, id: ALICE.into() }.into(),
+				},
+			]);
+			(query_id, message)
+		});
+
+		// The relay reports its own token as the parent's.
+		assert_eq!(response, Some(Response::Assets((Parent, amount).into())));
+		Relay::execute_with(|| {
+			assert_eq!(
+				RelayChainPalletBalances::free_balance(para_account_id(1)),
+				INITIAL_BALANCE - amount
+			);
+			assert_eq!(RelayChainPalletBalances::free_balance(ALICE), INITIAL_BALANCE + amount);
+		});
+	}
+
+	#[test]
+	fn report_error_from_error_handler() {
+		MockNet::reset();
+
+		let response = ask_relay(|| {
+			let (query_id, report) = report_error::<parachain::Runtime>(Parent, Here);
+			(query_id, Xcm(vec![SetErrorHandler(Xcm(vec![report])), Trap(7)]))
+		});
+
+		assert_eq!(response, Some(Response::ExecutionResult(Some((1, XcmError::Trap(7))))));
+	}
+
+	#[test]
+	fn query_pallet_of_relay() {
+		MockNet::reset();
+
+		let response = ask_relay(|| {
+			let (query_id, query) =
+				query_pallet::<parachain::Runtime>(Parent, Here, "pallet_balances");
+			(query_id, Xcm(vec![query]))
+		});
+
+		let version = relay_chain::Balances::crate_version();
+		let balances = PalletInfo::new(
+			1,
+			b"Balances".to_vec(),
+			b"pallet_balances".to_vec(),
+			version.major.into(),
+			version.minor.into(),
+			version.patch.into(),
+		);
+		let balances = balances.unwrap();
+		assert_eq!(response, Some(Response::PalletsInfo(vec![balances].try_into().unwrap())));
+	}
+
+	#[test]
+	fn expect_pallet_guards_execution() {
+		MockNet::reset();
+
+		let expect_balances = |index, name: &[u8], crate_major| {
+			let data = VersionedXcm::from(Xcm::<()>(vec![
+				ExpectPallet {
+					index,
+					name: name.to_vec(),
+					module_name: b"pallet_balances".to_vec(),
+					crate_major,
+					min_crate_minor: 0,
+				},
+				ClearOrigin,
+			]))
+			.encode();
+			match replay(Channel::Dmp { to: 1 }, &data).outcome {
+				ReplayOutcome::Executed(outcome) => outcome.ensure_complete().map(|_| ()),
+				other => panic!("not executed: {:?}", other),
+			}
+		};
+
+		let major = u32::from(parachain::Balances::crate_version().major);
+		assert_eq!(expect_balances(1, b"Balances", major), Ok(()));
+		assert_eq!(expect_balances(1, b"Balances", major - 1), Err(XcmError::VersionIncompatible));
+		assert_eq!(expect_balances(1, b"Assets", major), Err(XcmError::NameMismatch));
+		assert_eq!(expect_balances(42, b"Balances", major), Err(XcmError::PalletNotFound));
+	}
+
+	/// The call `pallet_xcm` dispatches with the response, filling in its arguments.
+	fn notify() -> parachain::RuntimeCall {
+		parachain::mock_query_notifier::Call::notify { query_id: 0, response: Response::Null }
+			.into()
+	}
+
+	#[test]
+	fn notify_on_response() {
+		MockNet::reset();
+
+		let query_id = ParaA::execute_with(|| {
+			let response_info = expect_response::<parachain::Runtime>(Parent, Here, Some(notify()));
+			let query_id = response_info.query_id;
+			assert_ok!(ParachainPalletXcm::send_xcm(
+				Here,
+				Parent,
+				Xcm(vec![ReportError(response_info)])
+			));
+			query_id
+		});
+
+		ParaA::execute_with(|| {
+			let response = Response::ExecutionResult(None);
+			assert_eq!(
+				parachain::QueryNotifier::received_responses(),
+				vec![(query_id, response.clone())]
+			);
+			let expected: parachain::RuntimeEvent =
+				parachain::mock_query_notifier::Event::ResponseReceived(
+					Parent.into(),
+					query_id,
+					response,
+				)
+				.into();
+			assert!(parachain::para_events().contains(&expected));
+			// Notified responses aren't stored for `take_response`.
+			assert_eq!(take_response::<parachain::Runtime>(query_id), None);
+		});
+	}
+
+	#[test]
+	fn notify_query_times_out_without_response() {
+		MockNet::reset();
+
+		ParaA::execute_with(|| {
+			let query_id =
+				expect_response::<parachain::Runtime>(Parent, Here, Some(notify())).query_id;
+			parachain::para_roll_to(parachain::System::block_number() + QUERY_TIMEOUT as u64 + 1);
+
+			let now = parachain::System::block_number();
+			assert!(matches!(
+				ParachainPalletXcm::query(query_id),
+				Some(pallet_xcm::QueryStatus::Pending { timeout, .. }) if timeout < now
+			));
+			assert!(parachain::QueryNotifier::received_responses().is_empty());
+			assert_eq!(take_response::<parachain::Runtime>(query_id), None);
+		});
+	}
+
+	#[test]
+	fn unanswered_query_has_no_response() {
+		MockNet::reset();
+
+		let query_id = ParaA::execute_with(|| report_error::<parachain::Runtime>(Parent, Here).0);
+
+		assert_eq!(ParaA::execute_with(|| take_response::<parachain::Runtime>(query_id)), None);
+		Relay::execute_with(|| assert!(relay_chain::relay_events().is_empty()));
+	}
+}
@@ -18,8 +18,8 @@
 
 use frame_support::{
 	construct_runtime, parameter_types,
-	traits::{Everything, Nothing},
-	weights::Weight,
+	traits::{Equals, Everything, ProcessMessage, ProcessMessageError},
+	weights::{Weight, WeightMeter},
 };
 use frame_system::EnsureRoot;
 use sp_core::{ConstU32, ConstU64};
@@ -28,17 +28,25 @@ use sp_runtime::{traits::IdentityLookup, AccountId32};
 use frame_support::derive_impl;
 use polkadot_primitives::Id as ParaId;
 use polkadot_runtime_parachains::{
-	configuration, inclusion::AggregateMessageOrigin, origin, shared,
+	configuration,
+	inclusion::{AggregateMessageOrigin, UmpQueueId},
+	origin, shared,
 };
 use xcm::latest::prelude::*;
 use xcm_builder::{
 	AccountId32Aliases, AllowUnpaidExecutionFrom, ChildParachainAsNative,
 	ChildParachainConvertsVia, ChildSystemParachainAsSuperuser,
-	CurrencyAdapter as XcmCurrencyAdapter, FixedRateOfFungible, FixedWeightBounds, IsConcrete,
+	CurrencyAdapter as XcmCurrencyAdapter, DescribeAllTerminal, DescribeFamily,
+	FixedRateOfFungible, FixedWeightBounds, HashedDescription, IsConcrete, ProcessXcmMessage,
 	SignedAccountId32AsNative, SignedToAccountId32, SovereignSignedViaLocation,
 };
 use xcm_executor::{Config, XcmExecutor};
 
+use crate::{
+	aliasing::AliasChildLocation, bridge::ExportRouter, parachain::TrustedLockerCase,
+	versioning::VersionedRouter, BRIDGE_HUB_ID,
+};
+
 pub type AccountId = AccountId32;
 pub type Balance = u128;
 
@@ -97,8 +105,19 @@ parameter_types! {
 	pub UnitWeightCost: u64 = 1_000;
 }
 
-pub type SovereignAccountOf =
-	(ChildParachainConvertsVia<ParaId, AccountId>, AccountId32Aliases<KusamaNetwork, AccountId>);
+parameter_types! {
+	pub const BridgedNetwork: NetworkId = NetworkId::Polkadot;
+	pub BridgeHub: MultiLocation = Parachain(BRIDGE_HUB_ID).into();
+	pub BridgedNetworkViaBridgeHub: (MultiLocation, Junction) =
+		(BridgeHub::get(), GlobalConsensus(BridgedNetwork::get()));
+}
+
+pub type SovereignAccountOf = (
+	ChildParachainConvertsVia<ParaId, AccountId>,
+	AccountId32Aliases<KusamaNetwork, AccountId>,
+	// Accounts on the paras, e.g. the owners of the assets paras lock for the relay.
+	HashedDescription<AccountId, DescribeFamily<DescribeAllTerminal>>,
+);
 
 pub type LocalAssetTransactor =
 	XcmCurrencyAdapter<Balances, IsConcrete<KsmLocation>, SovereignAccountOf, AccountId, ()>;
@@ -116,7 +135,13 @@ parameter_types! {
 	pub const MaxInstructions: u32 = 100;
 }
 
-pub type XcmRouter = super::RelayChainXcmRouter;
+pub type XcmRouter = VersionedRouter<
+	XcmPallet,
+	(
+		super::RelayChainXcmRouter,
+		ExportRouter<UniversalLocation, BridgeHub, super::RelayChainXcmRouter, ()>,
+	),
+>;
 pub type Barrier = AllowUnpaidExecutionFrom<Everything>;
 
 pub struct XcmConfig;
@@ -133,22 +158,29 @@ impl Config for XcmConfig {
 	type Trader = FixedRateOfFungible<KsmPerSecond, ()>;
 	type ResponseHandler = XcmPallet;
 	type AssetTrap = XcmPallet;
-	type AssetLocker = ();
+	type AssetLocker = XcmPallet;
 	type AssetExchanger = ();
 	type AssetClaims = XcmPallet;
 	type SubscriptionService = XcmPallet;
-	type PalletInstancesInfo = ();
+	type PalletInstancesInfo = AllPalletsWithSystem;
 	type FeeManager = ();
 	type MaxAssetsIntoHolding = ConstU32<64>;
 	type MessageExporter = ();
-	type UniversalAliases = Nothing;
+	type UniversalAliases = Equals<BridgedNetworkViaBridgeHub>;
 	type CallDispatcher = RuntimeCall;
 	type SafeCallFilter = Everything;
-	type Aliasers = ();
+	type Aliasers = AliasChildLocation;
 }
 
 pub type LocalOriginToLocation = SignedToAccountId32<RuntimeOrigin, AccountId, KusamaNetwork>;
 
+parameter_types! {
+	pub RelayTokenForParaA: (MultiLocation, MultiAssetFilter) =
+		(Parachain(1).into(), Wild(AllOf { id: Concrete(KsmLocation::get()), fun: WildFungible }));
+}
+
+pub type TrustedLockers = TrustedLockerCase<RelayTokenForParaA>;
+
 impl pallet_xcm::Config for Runtime {
 	type RuntimeEvent = RuntimeEvent;
 	type SendXcmOrigin = xcm_builder::EnsureXcmOrigin<RuntimeOrigin, LocalOriginToLocation>;
@@ -164,17 +196,15 @@ impl pallet_xcm::Config for Runtime {
 	type RuntimeOrigin = RuntimeOrigin;
 	type RuntimeCall = RuntimeCall;
 	const VERSION_DISCOVERY_QUEUE_SIZE: u32 = 100;
-	type AdvertisedXcmVersion = pallet_xcm::CurrentXcmVersion;
+	type AdvertisedXcmVersion = XcmVersioner;
 	type Currency = Balances;
-	type CurrencyMatcher = ();
-	type TrustedLockers = ();
-	type SovereignAccountOf = ();
+	type CurrencyMatcher = IsConcrete<KsmLocation>;
+	type TrustedLockers = TrustedLockers;
+	type SovereignAccountOf = SovereignAccountOf;
 	type MaxLockers = ConstU32<8>;
 	type MaxRemoteLockConsumers = ConstU32<0>;
 	type RemoteLockConsumerIdentifier = ();
 	type WeightInfo = pallet_xcm::TestWeightInfo;
-	#[cfg(feature = "runtime-benchmarks")]
-	type ReachableDest = ReachableDest;
 	type AdminOrigin = EnsureRoot<AccountId>;
 }
 
@@ -184,8 +214,28 @@ parameter_types! {
 	pub const MessageQueueMaxStale: u32 = 8;
 }
 
-type MessageProcessor =
-	pallet_message_queue::mock_helpers::NoopMessageProcessor<AggregateMessageOrigin>;
+/// Executes the upward messages with the para they come from as origin.
+pub struct MessageProcessor;
+impl ProcessMessage for MessageProcessor {
+	type Origin = AggregateMessageOrigin;
+
+	fn process_message(
+		message: &[u8],
+		origin: Self::Origin,
+		meter: &mut WeightMeter,
+		id: &mut [u8; 32],
+	) -> Result<bool, ProcessMessageError> {
+		let para = match origin {
+			AggregateMessageOrigin::Ump(UmpQueueId::Para(para)) => para,
+		};
+		ProcessXcmMessage::<Junction, XcmExecutor<XcmConfig>, RuntimeCall>::process_message(
+			message,
+			Parachain(para.into()),
+			meter,
+			id,
+		)
+	}
+}
 
 impl pallet_message_queue::Config for Runtime {
 	type RuntimeEvent = RuntimeEvent;
@@ -204,6 +254,12 @@ impl pallet_message_queue::Config for Runtime {
 
 impl origin::Config for Runtime {}
 
+pub use crate::mock_version_changer;
+
+impl mock_version_changer::Config for Runtime {
+	type RuntimeEvent = RuntimeEvent;
+}
+
 type Block = frame_system::mocking::MockBlock<Runtime>;
 
 construct_runtime!(
@@ -213,10 +269,14 @@ construct_runtime!(
 		ParasOrigin: origin::{Pallet, Origin},
 		XcmPallet: pallet_xcm::{Pallet, Call, Storage, Event<T>, Origin},
 		MessageQueue: pallet_message_queue,
+		XcmVersioner: mock_version_changer::{Pallet, Storage, Event<T>},
 	}
 );
 
-use frame_support::traits::{OnFinalize, OnInitialize};
+use frame_support::traits::{OnFinalize, OnInitialize, OnRuntimeUpgrade};
+pub fn on_runtime_upgrade() {
+	XcmPallet::on_runtime_upgrade();
+}
 
 pub fn relay_roll_to(n: u64) {
 	while System::block_number() < n {
@@ -0,0 +1,202 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Replays captured XCM messages, e.g. exported with `debug-failed-ump --export`, in `MockNet`.
+//!
+//! The message is decoded and executed the way the receiving chain handles messages arriving
+//! over the given channel, with the origin that channel gives it. Messages it sends in turn are
+//! delivered by `MockNet` as usual.
+
+use core::fmt::Debug;
+use frame_support::weights::Weight;
+use xcm::{latest::prelude::*, VersionedXcm};
+use xcm_executor::XcmExecutor;
+
+use crate::{parachain, relay_chain, KusamaBridgeHub, ParaA, ParaB, Relay, TestExt, BRIDGE_HUB_ID};
+
+/// How a replayed message reaches the chain executing it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+	/// Sent upward by the para `from` to the relay.
+	Ump { from: u32 },
+	/// Sent downward by the relay to the para `to`.
+	Dmp { to: u32 },
+	/// Sent by the para `from` to its sibling `to`.
+	Xcmp { from: u32, to: u32 },
+}
+
+/// What happened to a replayed message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReplayOutcome {
+	/// The bytes are not a `VersionedXcm`.
+	InvalidFormat,
+	/// The message's XCM version cannot be converted to the latest one.
+	UnsupportedVersion,
+	/// `MockNet` has no para with this id to receive the message.
+	UnknownPara(u32),
+	/// The message was executed.
+	Executed(Outcome),
+}
+
+/// The result of replaying a message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Replay {
+	pub outcome: ReplayOutcome,
+	/// The events the receiving chain emitted while executing the message, as printed by `Debug`.
+	pub trace: Vec<String>,
+}
+
+/// Replays the encoded `VersionedXcm` `data` as a message sent over `channel`.
+pub fn replay(channel: Channel, data: &[u8]) -> Replay {
+	match channel {
+		Channel::Ump { from } => Relay::execute_with(|| {
+			execute::<relay_chain::XcmConfig, _>(Parachain(from), data, relay_chain::relay_events)
+		}),
+		Channel::Dmp { to } => execute_on_para(to, || {
+			execute::<parachain::XcmConfig, _>(Parent, data, parachain::para_events)
+		}),
+		Channel::Xcmp { from, to } => execute_on_para(to, || {
+			execute::<parachain::XcmConfig, _>(
+				(Parent, Parachain(from)),
+				data,
+				parachain::para_events,
+			)
+		}),
+	}
+}
+
+fn execute_on_para(para_id: u32, f: impl FnOnce() -> Replay) -> Replay {
+	match para_id {
+		1 => ParaA::execute_with(f),
+		2 => ParaB::execute_with(f),
+		BRIDGE_HUB_ID => KusamaBridgeHub::execute_with(f),
+		_ => Replay { outcome: ReplayOutcome::UnknownPara(para_id), trace: Vec::new() },
+	}
+}
+
+fn execute<Config: xcm_executor::Config, Event: Debug>(
+	origin: impl Into<MultiLocation>,
+	data: &[u8],
+	events: fn() -> Vec<Event>,
+) -> Replay {
+	let id = sp_io::hashing::blake2_256(data);
+	let emitted_before = events().len();
+
+	let outcome = match xcm_decoder::decode::<VersionedXcm<Config::RuntimeCall>>(data)
+		.map(Xcm::<Config::RuntimeCall>::try_from)
+	{
+		Err(_) => ReplayOutcome::InvalidFormat,
+		Ok(Err(())) => ReplayOutcome::UnsupportedVersion,
+		Ok(Ok(xcm)) => ReplayOutcome::Executed(XcmExecutor::<Config>::execute_xcm(
+			origin,
+			xcm,
+			id,
+			Weight::MAX,
+		)),
+	};
+
+	let trace = events()
+		.into_iter()
+		.skip(emitted_before)
+		.map(|event| format!("{:?}", event))
+		.collect();
+	Replay { outcome, trace }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{para_account_id, MockNet, RelayChainPalletBalances, ALICE, INITIAL_BALANCE};
+	use codec::Encode;
+
+	fn encode(xcm: Xcm<()>) -> Vec<u8> {
+		VersionedXcm::from(xcm).encode()
+	}
+
+	#[test]
+	fn replays_upward_message() {
+		MockNet::reset();
+
+		let amount = 100u128;
+		let data = encode(Xcm(vec![
+			WithdrawAsset((Here, amount).into()),
+			DepositAsset {
+				assets: AllCounted(1).into(),
+				beneficiary: AccountId32 { network: None, id: ALICE.into() }.into(),
+			},
+		]));
+
+		let replay = replay(Channel::Ump { from: 1 }, &data);
+		assert!(matches!(replay.outcome, ReplayOutcome::Executed(Outcome::Complete(_))));
+		assert!(!replay.trace.is_empty());
+
+		Relay::execute_with(|| {
+			assert_eq!(RelayChainPalletBalances::free_balance(ALICE), INITIAL_BALANCE + amount);
+			assert_eq!(
+				RelayChainPalletBalances::free_balance(para_account_id(1)),
+				INITIAL_BALANCE - amount
+			);
+		});
+	}
+
+	#[test]
+	fn replays_horizontal_message() {
+		MockNet::reset();
+
+		let data = encode(Xcm(vec![ClearOrigin, Trap(7)]));
+
+		let replay = replay(Channel::Xcmp { from: 1, to: 2 }, &data);
+		assert!(matches!(
+			replay.outcome,
+			ReplayOutcome::Executed(Outcome::Incomplete(_, XcmError::Trap(7)))
+		));
+	}
+
+	#[test]
+	fn reports_undecodable_downward_message() {
+		MockNet::reset();
+
+		assert_eq!(
+			replay(Channel::Dmp { to: 1 }, &[0xff, 0x00]),
+			Replay { outcome: ReplayOutcome::InvalidFormat, trace: Vec::new() }
+		);
+	}
+
+	#[test]
+	fn replays_downward_message_to_bridge_hub() {
+		MockNet::reset();
+
+		let data = encode(Xcm(vec![ClearOrigin, Trap(7)]));
+
+		let replay = replay(Channel::Dmp { to: BRIDGE_HUB_ID }, &data);
+		assert!(matches!(
+			replay.outcome,
+			ReplayOutcome::Executed(Outcome::Incomplete(_, XcmError::Trap(7)))
+		));
+	}
+
+	#[test]
+	fn reports_unknown_para() {
+		MockNet::reset();
+
+		let data = encode(Xcm(vec![ClearOrigin]));
+
+		assert_eq!(
+			replay(Channel::Xcmp { from: 1, to: 3 }, &data),
+			Replay { outcome: ReplayOutcome::UnknownPara(3), trace: Vec::new() }
+		);
+	}
+}
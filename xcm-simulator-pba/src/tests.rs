@@ -0,0 +1,152 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tests for `pallet-activity-runtime-xcm` installed in the parachain runtime.
+//!
+//! Run with `cargo test -p xcm-simulator-pba --features activity-pallet`.
+
+use super::*;
+use frame_support::assert_ok;
+use xcm::latest::prelude::*;
+use xcm_executor::traits::DropAssets;
+
+/// ALICE on ParaA as seen by its siblings and the relay, after `DescendOrigin`.
+fn alice_on_para_a(parents: u8) -> MultiLocation {
+	MultiLocation::new(
+		parents,
+		X2(Parachain(1), AccountId32 { network: Some(NetworkId::Kusama), id: ALICE.into() }),
+	)
+}
+
+#[test]
+fn trap_and_claim_on_para_a() {
+	MockNet::reset();
+
+	let amount = 100u128;
+	let assets: MultiAssets = (Parent, amount).into();
+
+	ParaA::execute_with(|| {
+		assert_ok!(ParachainActivityPallet::trap_assets(
+			parachain::RuntimeOrigin::signed(ALICE),
+			assets.clone()
+		));
+		assert_eq!(ParachainPalletBalances::free_balance(ALICE), INITIAL_BALANCE - amount);
+
+		assert_ok!(ParachainActivityPallet::claim_assets(
+			parachain::RuntimeOrigin::signed(ALICE),
+			assets,
+			Here.into()
+		));
+		assert_eq!(ParachainPalletBalances::free_balance(ALICE), INITIAL_BALANCE);
+	});
+}
+
+#[test]
+fn claim_remote_on_relay() {
+	MockNet::reset();
+
+	let amount = 100u128;
+
+	// Simulates assets left over by a failed message that ALICE sent from ParaA.
+	Relay::execute_with(|| {
+		RelayChainPalletXcm::drop_assets(
+			&alice_on_para_a(0),
+			MultiAsset::from((Here, amount)).into(),
+			&XcmContext::with_message_id([0; 32]),
+		);
+	});
+
+	ParaA::execute_with(|| {
+		assert_ok!(ParachainActivityPallet::claim_remote(
+			parachain::RuntimeOrigin::signed(ALICE),
+			Parent.into(),
+			(Here, amount).into(),
+			Here.into(),
+			0,
+			AccountId32 { network: None, id: ALICE.into() }.into(),
+		));
+	});
+
+	Relay::execute_with(|| {
+		assert!(relay_chain::relay_events().iter().any(|event| matches!(
+			event,
+			relay_chain::RuntimeEvent::XcmPallet(pallet_xcm::Event::AssetsClaimed { .. })
+		)));
+		assert_eq!(RelayChainPalletBalances::free_balance(ALICE), INITIAL_BALANCE + amount);
+	});
+}
+
+#[test]
+fn claim_remote_on_sibling() {
+	MockNet::reset();
+
+	let amount = 100u128;
+
+	// Simulates assets left over by a failed message that ALICE sent from ParaA.
+	ParaB::execute_with(|| {
+		ParachainPalletXcm::drop_assets(
+			&alice_on_para_a(1),
+			MultiAsset::from((Parent, amount)).into(),
+			&XcmContext::with_message_id([0; 32]),
+		);
+	});
+
+	ParaA::execute_with(|| {
+		assert_ok!(ParachainActivityPallet::claim_remote(
+			parachain::RuntimeOrigin::signed(ALICE),
+			(Parent, Parachain(2)).into(),
+			(Parent, amount).into(),
+			Here.into(),
+			0,
+			AccountId32 { network: None, id: ALICE.into() }.into(),
+		));
+	});
+
+	ParaB::execute_with(|| {
+		assert!(parachain::para_events().iter().any(|event| matches!(
+			event,
+			parachain::RuntimeEvent::MsgQueue(parachain::mock_msg_queue::Event::Success(_))
+		)));
+		assert_eq!(ParachainPalletBalances::free_balance(ALICE), INITIAL_BALANCE + amount);
+	});
+}
+
+#[test]
+fn claim_remote_without_trap_fails_on_sibling() {
+	MockNet::reset();
+
+	ParaA::execute_with(|| {
+		assert_ok!(ParachainActivityPallet::claim_remote(
+			parachain::RuntimeOrigin::signed(ALICE),
+			(Parent, Parachain(2)).into(),
+			(Parent, 100u128).into(),
+			Here.into(),
+			0,
+			AccountId32 { network: None, id: ALICE.into() }.into(),
+		));
+	});
+
+	ParaB::execute_with(|| {
+		assert!(parachain::para_events().iter().any(|event| matches!(
+			event,
+			parachain::RuntimeEvent::MsgQueue(parachain::mock_msg_queue::Event::Fail(
+				_,
+				XcmError::UnknownClaim
+			))
+		)));
+		assert_eq!(ParachainPalletBalances::free_balance(ALICE), INITIAL_BALANCE);
+	});
+}
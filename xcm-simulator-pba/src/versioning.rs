@@ -0,0 +1,401 @@
+// Copyright 2021 Parity Technologies (UK) Ltd.
+// This file is part of Polkadot.
+
+// Polkadot is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Polkadot is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Polkadot.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Drives XCM version negotiation between the chains of `MockNet`.
+//!
+//! Every chain advertises the version set in its `XcmVersioner`. Subscribing sends
+//! `SubscribeVersion` to the target, which answers with a `QueryResponse` holding its version
+//! (`SupportedVersionChanged` on the subscriber). Upgrading a chain to a new version makes it
+//! notify its subscribers on the next block (`VersionChangeNotified` on the upgraded chain,
+//! `SupportedVersionChanged` on the subscribers).
+//!
+//! Both runtimes send through a [`VersionedRouter`], so every message is wrapped by `pallet_xcm`
+//! at the version its sender knows the destination to support, and recorded with it. Chains send
+//! at the latest version to destinations whose version they don't know, unless given a default
+//! version with [`relay_default_version`] or [`para_default_version`], and such messages are
+//! recorded as [`SentMessage::unknown_version`].
+
+use codec::Encode;
+use core::marker::PhantomData;
+use frame_support::{
+	assert_ok,
+	storage::{storage_prefix, unhashed},
+	traits::PalletInfoAccess,
+	Blake2_128Concat, StorageHasher, Twox64Concat,
+};
+use std::cell::RefCell;
+use xcm::{
+	latest::prelude::*, Version as XcmVersion, VersionedMultiLocation, VersionedXcm, WrapVersion,
+};
+
+use crate::{parachain, relay_chain, ParachainPalletXcm, Relay, RelayChainPalletXcm, TestExt};
+
+/// A message sent through a [`VersionedRouter`], at the version it was wrapped at.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SentMessage {
+	pub destination: MultiLocation,
+	pub version: XcmVersion,
+	pub message: VersionedXcm<()>,
+	/// The sender knew no version to wrap messages to the destination at, neither the
+	/// destination's nor a default one, so `pallet_xcm` would have refused to send the message.
+	pub unknown_version: bool,
+}
+
+std::thread_local! {
+	static SENT_MESSAGES: RefCell<Vec<SentMessage>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Takes the messages sent through a [`VersionedRouter`] so far, on any chain.
+pub fn take_sent_messages() -> Vec<SentMessage> {
+	SENT_MESSAGES.with(|sent| sent.take())
+}
+
+/// Sends messages through `Router` after wrapping them with `Wrapper`, usually the sender's
+/// `pallet_xcm`, at the version it knows the destination to support.
+///
+/// Messages which cannot be converted to that version fail with `DestinationUnsupported`. The
+/// simulator's message buses only carry the latest version, so `Router` delivers the wrapped
+/// message converted back to it: the destination executes what an older chain would get, without
+/// what the older version cannot express. The wrapped message is recorded, see
+/// [`take_sent_messages`].
+///
+/// Destinations whose version `Wrapper` neither knows nor has a default for are unreachable for
+/// `pallet_xcm`, which notes them to discover their version. Until then, they get the latest
+/// version, flagged with [`SentMessage::unknown_version`], so that chains don't need to negotiate
+/// versions before exchanging messages.
+pub struct VersionedRouter<Wrapper, Router>(PhantomData<(Wrapper, Router)>);
+
+impl<Wrapper: WrapVersion, Router: SendXcm> SendXcm for VersionedRouter<Wrapper, Router> {
+	type Ticket = (Router::Ticket, SentMessage);
+
+	fn validate(
+		destination: &mut Option<MultiLocation>,
+		message: &mut Option<Xcm<()>>,
+	) -> SendResult<Self::Ticket> {
+		let (dest, msg) = match (*destination, message.clone()) {
+			(Some(dest), Some(msg)) => (dest, msg),
+			_ => return Err(SendError::MissingArgument),
+		};
+		let (wrapped, unknown_version) = match Wrapper::wrap_version(&dest, msg.clone()) {
+			Ok(wrapped) => (wrapped, false),
+			// Any version can express an empty message, so wrapping one only fails without a
+			// version to wrap at. Otherwise the message doesn't convert to that version.
+			Err(()) if Wrapper::wrap_version(&dest, Xcm::<()>::new()).is_err() =>
+				(VersionedXcm::from(msg), true),
+			Err(()) => return Err(SendError::DestinationUnsupported),
+		};
+		let delivered =
+			Xcm::try_from(wrapped.clone()).map_err(|()| SendError::DestinationUnsupported)?;
+		let (ticket, price) = Router::validate(&mut Some(dest), &mut Some(delivered))?;
+		*destination = None;
+		*message = None;
+		let version = match wrapped {
+			VersionedXcm::V2(_) => 2,
+			VersionedXcm::V3(_) => 3,
+		};
+		let sent = SentMessage { destination: dest, version, message: wrapped, unknown_version };
+		Ok(((ticket, sent), price))
+	}
+
+	fn deliver((ticket, sent): Self::Ticket) -> Result<XcmHash, SendError> {
+		let hash = Router::deliver(ticket)?;
+		SENT_MESSAGES.with(|messages| messages.borrow_mut().push(sent));
+		Ok(hash)
+	}
+}
+
+/// Makes the relay subscribe to the version of the para `para`.
+pub fn relay_subscribe(para: u32) {
+	Relay::execute_with(|| {
+		assert_ok!(RelayChainPalletXcm::force_subscribe_version_notify(
+			relay_chain::RuntimeOrigin::root(),
+			Box::new(VersionedMultiLocation::V3(Parachain(para).into())),
+		));
+	});
+}
+
+/// Makes `Para` subscribe to the version of `dest`, the relay or one of its siblings.
+pub fn para_subscribe<Para: TestExt>(dest: impl Into<MultiLocation>) {
+	let dest = dest.into();
+	Para::execute_with(|| {
+		assert_ok!(ParachainPalletXcm::force_subscribe_version_notify(
+			parachain::RuntimeOrigin::root(),
+			Box::new(VersionedMultiLocation::V3(dest)),
+		));
+	});
+}
+
+/// Makes the relay send at `version` to destinations whose version it doesn't know, and start
+/// negotiating their version, like a production relay.
+pub fn relay_default_version(version: XcmVersion) {
+	Relay::execute_with(|| {
+		assert_ok!(RelayChainPalletXcm::force_default_xcm_version(
+			relay_chain::RuntimeOrigin::root(),
+			Some(version),
+		));
+	});
+}
+
+/// Makes `Para` send at `version` to destinations whose version it doesn't know, and start
+/// negotiating their version, like a production parachain.
+pub fn para_default_version<Para: TestExt>(version: XcmVersion) {
+	Para::execute_with(|| {
+		assert_ok!(ParachainPalletXcm::force_default_xcm_version(
+			parachain::RuntimeOrigin::root(),
+			Some(version),
+		));
+	});
+}
+
+/// Makes the `pallet_xcm` of runtime `T` notify its subscribers of its version on the next block.
+///
+/// `pallet_xcm` only notifies them while migrating its storage, which it doesn't start on runtime
+/// upgrades, so the migration is started the way its own tests do.
+fn start_version_notifications<T: pallet_xcm::Config>() {
+	let pallet_name = <pallet_xcm::Pallet<T> as PalletInfoAccess>::name();
+	let key = storage_prefix(pallet_name.as_bytes(), b"CurrentMigration");
+	unhashed::put(&key, &pallet_xcm::VersionMigrationStage::default());
+}
+
+/// Upgrades the relay to advertise `version` and lets it notify its subscribers.
+pub fn upgrade_relay(version: XcmVersion) {
+	Relay::execute_with(|| {
+		relay_chain::XcmVersioner::set_version(version);
+		relay_chain::on_runtime_upgrade();
+		start_version_notifications::<relay_chain::Runtime>();
+		relay_chain::relay_roll_to(relay_chain::System::block_number() + 1);
+	});
+}
+
+/// Upgrades `Para` to advertise `version` and lets it notify its subscribers.
+pub fn upgrade_para<Para: TestExt>(version: XcmVersion) {
+	Para::execute_with(|| {
+		parachain::XcmVersioner::set_version(version);
+		parachain::on_runtime_upgrade();
+		start_version_notifications::<parachain::Runtime>();
+		parachain::para_roll_to(parachain::System::block_number() + 1);
+	});
+}
+
+/// The version the `pallet_xcm` of runtime `T` knows `dest` to support, which it doesn't expose.
+fn known_version<T: pallet_xcm::Config>(dest: MultiLocation) -> Option<XcmVersion> {
+	let pallet_name = <pallet_xcm::Pallet<T> as PalletInfoAccess>::name();
+	let mut key = storage_prefix(pallet_name.as_bytes(), b"SupportedVersion").to_vec();
+	key.extend(Twox64Concat::hash(&xcm::latest::VERSION.encode()));
+	key.extend(Blake2_128Concat::hash(&VersionedMultiLocation::from(dest).encode()));
+	unhashed::get(&key)
+}
+
+/// The version the relay knows the para `para` to support, if it was told any.
+pub fn relay_known_version(para: u32) -> Option<XcmVersion> {
+	Relay::execute_with(|| known_version::<relay_chain::Runtime>(Parachain(para).into()))
+}
+
+/// The version `Para` knows `dest` to support, if it was told any.
+pub fn para_known_version<Para: TestExt>(dest: impl Into<MultiLocation>) -> Option<XcmVersion> {
+	let dest = dest.into();
+	Para::execute_with(|| known_version::<parachain::Runtime>(dest))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{MockNet, ParaA, ParaB};
+	use frame_support::weights::Weight;
+	use xcm::latest::send_xcm;
+
+	#[test]
+	fn relay_learns_para_version() {
+		MockNet::reset();
+
+		ParaA::execute_with(|| parachain::XcmVersioner::set_version(2));
+		relay_subscribe(1);
+
+		assert_eq!(relay_known_version(1), Some(2));
+		assert_eq!(relay_known_version(2), None);
+		Relay::execute_with(|| {
+			let expected: relay_chain::RuntimeEvent = pallet_xcm::Event::SupportedVersionChanged {
+				location: Parachain(1).into(),
+				version: 2,
+			}
+			.into();
+			assert!(relay_chain::relay_events().contains(&expected));
+		});
+	}
+
+	#[test]
+	fn para_upgrade_notifies_relay() {
+		MockNet::reset();
+
+		ParaA::execute_with(|| parachain::XcmVersioner::set_version(xcm::latest::VERSION));
+		relay_subscribe(1);
+		assert_eq!(relay_known_version(1), Some(xcm::latest::VERSION));
+
+		upgrade_para::<ParaA>(2);
+
+		assert_eq!(relay_known_version(1), Some(2));
+		ParaA::execute_with(|| {
+			assert!(parachain::para_events().iter().any(|event| matches!(
+				event,
+				parachain::RuntimeEvent::PolkadotXcm(pallet_xcm::Event::VersionChangeNotified {
+					destination,
+					result: 2,
+					..
+				}) if *destination == Parent.into()
+			)));
+		});
+	}
+
+	#[test]
+	fn relay_upgrade_notifies_paras() {
+		MockNet::reset();
+
+		Relay::execute_with(|| relay_chain::XcmVersioner::set_version(xcm::latest::VERSION));
+		para_subscribe::<ParaA>(Parent);
+		para_subscribe::<ParaB>(Parent);
+		assert_eq!(para_known_version::<ParaA>(Parent), Some(xcm::latest::VERSION));
+		upgrade_relay(2);
+
+		assert_eq!(para_known_version::<ParaA>(Parent), Some(2));
+		assert_eq!(para_known_version::<ParaB>(Parent), Some(2));
+	}
+
+	#[test]
+	fn siblings_learn_each_other_versions() {
+		MockNet::reset();
+
+		ParaA::execute_with(|| parachain::XcmVersioner::set_version(xcm::latest::VERSION));
+		ParaB::execute_with(|| parachain::XcmVersioner::set_version(2));
+		para_subscribe::<ParaA>((Parent, Parachain(2)));
+		para_subscribe::<ParaB>((Parent, Parachain(1)));
+
+		assert_eq!(para_known_version::<ParaA>((Parent, Parachain(2))), Some(2));
+		assert_eq!(para_known_version::<ParaB>((Parent, Parachain(1))), Some(xcm::latest::VERSION));
+	}
+
+	#[test]
+	fn messages_are_sent_at_the_known_version() {
+		MockNet::reset();
+
+		ParaA::execute_with(|| parachain::XcmVersioner::set_version(2));
+		relay_subscribe(1);
+		take_sent_messages();
+
+		Relay::execute_with(|| {
+			assert_ok!(send_xcm::<relay_chain::XcmRouter>(
+				Parachain(1).into(),
+				Xcm(vec![ClearOrigin])
+			));
+			assert_ok!(send_xcm::<relay_chain::XcmRouter>(
+				Parachain(2).into(),
+				Xcm(vec![ClearOrigin])
+			));
+		});
+
+		let versions: Vec<_> = take_sent_messages()
+			.into_iter()
+			.map(|sent| (sent.destination, sent.version))
+			.collect();
+		assert_eq!(
+			versions,
+			vec![(Parachain(1).into(), 2), (Parachain(2).into(), xcm::latest::VERSION)]
+		);
+	}
+
+	#[test]
+	fn default_version_applies_to_unknown_destinations() {
+		MockNet::reset();
+
+		relay_default_version(2);
+		take_sent_messages();
+
+		Relay::execute_with(|| {
+			assert_ok!(send_xcm::<relay_chain::XcmRouter>(
+				Parachain(2).into(),
+				Xcm(vec![ClearOrigin])
+			));
+		});
+
+		let versions: Vec<_> = take_sent_messages().into_iter().map(|sent| sent.version).collect();
+		assert_eq!(versions, vec![2]);
+	}
+
+	#[test]
+	fn messages_to_unknown_destinations_are_flagged() {
+		MockNet::reset();
+
+		ParaA::execute_with(|| parachain::XcmVersioner::set_version(2));
+		relay_subscribe(1);
+		take_sent_messages();
+
+		Relay::execute_with(|| {
+			for para in [1, 2] {
+				assert_ok!(send_xcm::<relay_chain::XcmRouter>(
+					Parachain(para).into(),
+					Xcm(vec![ClearOrigin])
+				));
+			}
+		});
+
+		let flags: Vec<_> = take_sent_messages()
+			.into_iter()
+			.map(|sent| (sent.version, sent.unknown_version))
+			.collect();
+		assert_eq!(flags, vec![(2, false), (xcm::latest::VERSION, true)]);
+	}
+
+	#[test]
+	fn destination_executes_the_downgraded_message() {
+		MockNet::reset();
+
+		ParaA::execute_with(|| parachain::XcmVersioner::set_version(2));
+		relay_subscribe(1);
+
+		// v2 weights have no proof size.
+		let weight_limit = Limited(Weight::from_parts(1_000, 1));
+		Relay::execute_with(|| {
+			assert_ok!(send_xcm::<relay_chain::XcmRouter>(
+				Parachain(1).into(),
+				Xcm(vec![BuyExecution { fees: (Here, 1).into(), weight_limit }])
+			));
+		});
+
+		ParaA::execute_with(|| {
+			let received = parachain::MsgQueue::received_dmp();
+			assert!(matches!(
+				received.last().map(|xcm| &xcm.0[..]),
+				Some([BuyExecution { weight_limit: Limited(weight), .. }])
+					if weight.ref_time() == 1_000 && weight.proof_size() != 1
+			));
+		});
+	}
+
+	#[test]
+	fn impossible_downgrade_is_unsupported() {
+		MockNet::reset();
+
+		ParaA::execute_with(|| parachain::XcmVersioner::set_version(2));
+		relay_subscribe(1);
+
+		Relay::execute_with(|| {
+			// `ClearTopic` only exists since v3.
+			assert_eq!(
+				send_xcm::<relay_chain::XcmRouter>(Parachain(1).into(), Xcm(vec![ClearTopic])),
+				Err(SendError::DestinationUnsupported)
+			);
+		});
+	}
+}